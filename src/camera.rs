@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use egui::{Pos2, Rect, Vec2};
+use egui_graphs::Metadata;
+
+const TWEEN_DURATION: Duration = Duration::from_millis(250);
+
+/// Smooth transition of the view towards a target center and zoom
+#[derive(Clone, Debug)]
+pub struct CameraTween {
+    start: Duration,
+    from_center: Pos2,
+    from_zoom: f32,
+    to_center: Pos2,
+    to_zoom: Option<f32>,
+}
+
+impl CameraTween {
+    /// Starts a tween to the canvas position `to_center`, keeping the zoom if `to_zoom` is `None`
+    pub fn new(start: Duration, to_center: Pos2, to_zoom: Option<f32>) -> Self {
+        Self {
+            start,
+            // the starting view is read on the first applied frame
+            from_center: Pos2::NAN,
+            from_zoom: f32::NAN,
+            to_center,
+            to_zoom,
+        }
+    }
+
+    /// Moves the view of the graph shown in `rect`, returns whether the tween is finished
+    pub fn apply(&mut self, ui: &mut egui::Ui, rect: Rect, now: Duration) -> bool {
+        let mut meta = Metadata::get(ui);
+        if self.from_zoom.is_nan() {
+            self.from_zoom = meta.zoom;
+            self.from_center = screen_center_to_canvas(rect, meta.pan, meta.zoom);
+        }
+
+        let t = (now.saturating_sub(self.start).as_secs_f32() / TWEEN_DURATION.as_secs_f32()).min(1.);
+        let t = ease_in_out_cubic(t);
+
+        // interpolate zoom geometrically so zooming feels uniform
+        let to_zoom = self.to_zoom.unwrap_or(self.from_zoom);
+        let zoom = (self.from_zoom.ln() + (to_zoom.ln() - self.from_zoom.ln()) * t).exp();
+        let center = self.from_center + (self.to_center - self.from_center) * t;
+
+        meta.zoom = zoom;
+        meta.pan = rect.center().to_vec2() - center.to_vec2() * zoom;
+        meta.store_into_ui(ui);
+
+        t >= 1.
+    }
+}
+
+/// Zooms and pans the view of the graph shown in `rect` so that the canvas rect `bounds` fits with `padding` screen pixels around it
+pub fn fit_to_rect(ui: &mut egui::Ui, rect: Rect, bounds: Rect, padding: f32) {
+    let mut meta = Metadata::get(ui);
+    let available = (rect.size() - Vec2::splat(2. * padding)).max(Vec2::splat(1.));
+    let size = bounds.size().max(Vec2::splat(1.));
+    meta.zoom = (available.x / size.x).min(available.y / size.y);
+    meta.pan = rect.center().to_vec2() - bounds.center().to_vec2() * meta.zoom;
+    meta.store_into_ui(ui);
+}
+
+fn screen_center_to_canvas(rect: Rect, pan: Vec2, zoom: f32) -> Pos2 {
+    ((rect.center().to_vec2() - pan) / zoom).to_pos2()
+}
+
+fn ease_in_out_cubic(t: f32) -> f32 {
+    if t < 0.5 {
+        4. * t * t * t
+    } else {
+        1. - (-2. * t + 2.).powi(3) / 2.
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use egui::Color32;
+use egui_graphs::{Edge, Node};
+use serde::{Deserialize, Serialize};
+
+use crate::G;
+
+/// Whether a node or edge is present in the graph compared against
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiffStatus {
+    Added,
+    Removed,
+    Unchanged,
+}
+
+impl DiffStatus {
+    pub fn color(self) -> Color32 {
+        match self {
+            DiffStatus::Added => Color32::from_rgb(60, 190, 80),
+            // removed nodes and edges are drawn as faint ghosts
+            DiffStatus::Removed => Color32::from_rgb(220, 50, 50).gamma_multiply(0.35),
+            DiffStatus::Unchanged => Color32::GRAY,
+        }
+    }
+}
+
+/// Summary of comparing the current graph with another extraction
+#[derive(Clone, Debug, Default)]
+pub struct GraphDiff {
+    pub added_nodes: usize,
+    pub removed_nodes: usize,
+    pub added_edges: usize,
+    pub removed_edges: usize,
+}
+
+/// Adds everything from `other` missing in `g` and marks all nodes and edges by whether they are in `g`, `other` or both
+pub fn diff_graph(g: &mut G, other: G) -> GraphDiff {
+    for ni in g.g.node_indices().collect::<Vec<_>>() {
+        g.g[ni].payload_mut().diff = Some(DiffStatus::Removed);
+    }
+    for ei in g.g.edge_indices().collect::<Vec<_>>() {
+        g.g[ei].payload_mut().diff = Some(DiffStatus::Removed);
+    }
+
+    let mut indices = g.g.node_indices().map(|ni| (g.g[ni].payload().name.clone(), ni)).collect::<HashMap<_, _>>();
+    let mut mapping = HashMap::new();
+    for oni in other.g.node_indices() {
+        let node = &other.g[oni];
+        let ni = match indices.get(&node.payload().name) {
+            Some(&ni) => {
+                g.g[ni].payload_mut().diff = Some(DiffStatus::Unchanged);
+                ni
+            }
+            None => {
+                let mut payload = node.payload().clone();
+                payload.diff = Some(DiffStatus::Added);
+                let ni = g.g.add_node(Node::new(payload).with_label(node.payload().name.clone()));
+                g.g[ni].bind(ni, node.location());
+                indices.insert(node.payload().name.clone(), ni);
+                ni
+            }
+        };
+        mapping.insert(oni, ni);
+    }
+
+    for oei in other.g.edge_indices() {
+        let (start, end) = other.g.edge_endpoints(oei).unwrap();
+        let (start, end) = (mapping[&start], mapping[&end]);
+        match g.g.find_edge(start, end) {
+            Some(ei) => g.g[ei].payload_mut().diff = Some(DiffStatus::Unchanged),
+            None => {
+                let mut payload = other.g[oei].payload().clone();
+                payload.diff = Some(DiffStatus::Added);
+                let ei = g.g.add_edge(start, end, Edge::new(payload));
+                g.g.edge_weight_mut(ei).unwrap().bind(ei, 1);
+            }
+        }
+    }
+
+    let mut res = GraphDiff::default();
+    for node in g.g.node_weights() {
+        match node.payload().diff {
+            Some(DiffStatus::Added) => res.added_nodes += 1,
+            Some(DiffStatus::Removed) => res.removed_nodes += 1,
+            _ => {}
+        }
+    }
+    for edge in g.g.edge_weights() {
+        match edge.payload().diff {
+            Some(DiffStatus::Added) => res.added_edges += 1,
+            Some(DiffStatus::Removed) => res.removed_edges += 1,
+            _ => {}
+        }
+    }
+    res
+}
+
+/// Removes the nodes and edges added by a comparison and the marks of the others
+pub fn clear_diff(g: &mut G) {
+    g.g.retain_edges(|g, ei| g[ei].payload().diff != Some(DiffStatus::Added));
+    g.g.retain_nodes(|g, ni| g[ni].payload().diff != Some(DiffStatus::Added));
+    for ni in g.g.node_indices().collect::<Vec<_>>() {
+        g.g[ni].payload_mut().diff = None;
+    }
+    for ei in g.g.edge_indices().collect::<Vec<_>>() {
+        g.g[ei].payload_mut().diff = None;
+    }
+}
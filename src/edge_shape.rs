@@ -8,12 +8,21 @@ use egui_graphs::{DisplayEdge, DisplayNode, DrawContext, EdgeProps, Node};
 use petgraph::{stable_graph::IndexType, EdgeType};
 use serde::{Deserialize, Serialize};
 
-use crate::{col_ft, NodePayload};
+use crate::{theme_color, DiffStatus, DisplaySettings, EdgePayload, NodePayload};
+
+/// Distance between the dots of an animated edge in canvas units
+const FLOW_DOT_SPACING: f32 = 40.;
+/// Speed of the dots of an animated edge in canvas units per second
+const FLOW_SPEED: f32 = 60.;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EdgeShape {
     pub order: usize,
     pub selected: bool,
+    #[serde(default = "EdgeShape::default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    diff: Option<DiffStatus>,
 
     width: f32,
     tip_size: f32,
@@ -22,11 +31,13 @@ pub struct EdgeShape {
     loop_size: f32,
 }
 
-impl<E: Clone> From<EdgeProps<E>> for EdgeShape {
-    fn from(edge: EdgeProps<E>) -> Self {
+impl From<EdgeProps<EdgePayload>> for EdgeShape {
+    fn from(edge: EdgeProps<EdgePayload>) -> Self {
         Self {
             order: edge.order,
             selected: edge.selected,
+            weight: edge.payload.weight,
+            diff: edge.payload.diff,
 
             width: 2.,
             tip_size: 15.,
@@ -37,13 +48,19 @@ impl<E: Clone> From<EdgeProps<E>> for EdgeShape {
     }
 }
 
-impl<E: Clone, Ty: EdgeType, Ix: IndexType, D: DisplayNode<NodePayload, E, Ty, Ix>>
-    DisplayEdge<NodePayload, E, Ty, Ix, D> for EdgeShape
+impl EdgeShape {
+    fn default_weight() -> u32 {
+        1
+    }
+}
+
+impl<Ty: EdgeType, Ix: IndexType, D: DisplayNode<NodePayload, EdgePayload, Ty, Ix>>
+    DisplayEdge<NodePayload, EdgePayload, Ty, Ix, D> for EdgeShape
 {
     fn is_inside(
         &self,
-        _start: &Node<NodePayload, E, Ty, Ix, D>,
-        _end: &Node<NodePayload, E, Ty, Ix, D>,
+        _start: &Node<NodePayload, EdgePayload, Ty, Ix, D>,
+        _end: &Node<NodePayload, EdgePayload, Ty, Ix, D>,
         _pos: egui::Pos2,
     ) -> bool {
         //unclickable
@@ -52,40 +69,48 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType, D: DisplayNode<NodePayload, E, Ty, I
 
     fn shapes(
         &mut self,
-        start: &Node<NodePayload, E, Ty, Ix, D>,
-        end: &Node<NodePayload, E, Ty, Ix, D>,
+        start: &Node<NodePayload, EdgePayload, Ty, Ix, D>,
+        end: &Node<NodePayload, EdgePayload, Ty, Ix, D>,
         ctx: &DrawContext,
     ) -> Vec<egui::Shape> {
         let _style = match self.selected {
             true => ctx.ctx.style().visuals.widgets.active,
             false => ctx.ctx.style().visuals.widgets.inactive,
         };
-        let mut color = if ctx.ctx.style().visuals.dark_mode {
-            col_ft(start.payload().comp_color().map(|x| 1. - x))
-        } else {
-            col_ft(start.payload().comp_color().map(|x| x.sqrt()))
-        };
+        let display_settings = DisplaySettings::load_from_ctx(ctx.ctx);
+        let mut color = theme_color(start.payload().comp_color(), ctx.ctx.style().visuals.dark_mode);
         color = Color32::from_rgba_unmultiplied(
             color.r(),
             color.g(),
             color.b(),
             if end.selected() {
-                230
+                display_settings.edge_alpha_selected
             } else {
                 if ctx.ctx.style().visuals.dark_mode {
-                    50
+                    display_settings.edge_alpha_dark
                 } else {
-                    180
+                    display_settings.edge_alpha_light
                 }
             },
         );
+        if start.payload().cycle.is_some() && start.payload().cycle == end.payload().cycle {
+            color = Color32::from_rgba_unmultiplied(220, 40, 40, color.a());
+        }
+        if let (true, Some(diff)) = (display_settings.show_diff, self.diff) {
+            color = diff.color().gamma_multiply(color.a() as f32 / 255.);
+        }
+        if start.payload().dimmed || end.payload().dimmed {
+            color = color.gamma_multiply(0.2);
+        }
 
-        let mp = start.payload().size.min(end.payload().size);
+        // scale with the drawn nodes, so edges follow the node size settings
+        let mp = start.payload().display_size.min(end.payload().display_size);
+        let width = self.width * (self.weight as f32).sqrt();
 
         if start.id() == end.id() {
             // draw loop
             let node_size = node_size(start);
-            let stroke = Stroke::new(self.width * ctx.meta.zoom * mp, color);
+            let stroke = Stroke::new(width * ctx.meta.zoom * mp, color);
             return vec![shape_looped(
                 ctx.meta.canvas_to_screen_size(node_size),
                 ctx.meta.canvas_to_screen_pos(start.location()),
@@ -104,7 +129,7 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType, D: DisplayNode<NodePayload, E, Ty, I
         let edge_start = start_connector_point;
         let edge_end = end_connector_point - self.tip_size * dir;
 
-        let stroke_edge = Stroke::new(self.width * mp * ctx.meta.zoom, color);
+        let stroke_edge = Stroke::new(width * mp * ctx.meta.zoom, color);
         let stroke_tip = Stroke::new(0., color);
         // if self.order == 0 {
         // draw straight edge
@@ -120,6 +145,29 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType, D: DisplayNode<NodePayload, E, Ty, I
             return vec![line];
         }
 
+        // show direction by dots flowing towards the target instead of a tip
+        let hovered = display_settings.hovered.is_some_and(|ni| ni.index() == start.id().index() || ni.index() == end.id().index());
+        let animate = !display_settings.large_graph || start.selected() || end.selected() || hovered;
+        if display_settings.edge_flow && animate {
+            let flow_start = ctx.meta.canvas_to_screen_pos(edge_start);
+            let flow_end = ctx.meta.canvas_to_screen_pos(tip_end);
+            let flow_dir = (flow_end - flow_start).normalized();
+            let len = (flow_end - flow_start).length();
+            // keep a minimal on-screen distance, so zoomed out views don't draw too many dots
+            let spacing = (FLOW_DOT_SPACING * ctx.meta.zoom * mp).max(8.);
+            let time = ctx.ctx.input(|i| i.time) as f32;
+            let dot_radius = stroke_edge.width * 1.5;
+
+            let line = Shape::line_segment([flow_start, flow_end], stroke_edge);
+            let mut res = vec![line];
+            let mut dis = (time * FLOW_SPEED * ctx.meta.zoom) % spacing;
+            while dis < len {
+                res.push(Shape::circle_filled(flow_start + flow_dir * dis, dot_radius, color));
+                dis += spacing;
+            }
+            return res;
+        }
+
         let tip_start_1 = tip_end - mp * self.tip_size * rotate_vector(dir, self.tip_angle);
         let tip_start_2 = tip_end - mp * self.tip_size * rotate_vector(dir, -self.tip_angle);
 
@@ -182,9 +230,11 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType, D: DisplayNode<NodePayload, E, Ty, I
         // vec![line_curved.into(), line_curved_tip]
     }
 
-    fn update(&mut self, state: &EdgeProps<E>) {
+    fn update(&mut self, state: &EdgeProps<EdgePayload>) {
         self.order = state.order;
         self.selected = state.selected;
+        self.weight = state.payload.weight;
+        self.diff = state.payload.diff;
     }
 }
 
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, fmt::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ConstCategory, G};
+
+/// Tool agnostic description of a computed layout
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    pub nodes: Vec<LayoutNode>,
+    pub edges: Vec<LayoutEdge>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutNode {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    /// Computed color as RGB in 0..=1, before the dark or light theme is applied
+    pub color: [f32; 3],
+    pub size: f32,
+    pub category: ConstCategory,
+}
+
+/// Edge from the referenced constant to the one referencing it, endpoints are indices into `Layout::nodes`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutEdge {
+    pub source: usize,
+    pub target: usize,
+    pub weight: u32,
+}
+
+/// Positions and colors of all nodes together with the edges as JSON, see [`Layout`]
+///
+/// Collapsed namespace groups are left out together with their edges, expand them to export their constants.
+pub fn export_layout(g: &G) -> String {
+    let mut indices = HashMap::new();
+    let mut nodes = vec![];
+    for ni in g.g.node_indices() {
+        let node = &g.g[ni];
+        if node.payload().group.is_some() {
+            continue;
+        }
+        let pos = node.location();
+        indices.insert(ni, nodes.len());
+        nodes.push(LayoutNode {
+            name: node.payload().name.clone(),
+            x: pos.x,
+            y: pos.y,
+            color: node.payload().comp_color(),
+            size: node.payload().size,
+            category: node.payload().const_category.clone(),
+        });
+    }
+    let edges = g
+        .g
+        .edge_indices()
+        .filter_map(|ei| {
+            let (start, end) = g.g.edge_endpoints(ei).unwrap();
+            Some(LayoutEdge {
+                source: *indices.get(&start)?,
+                target: *indices.get(&end)?,
+                weight: g.g[ei].payload().weight,
+            })
+        })
+        .collect();
+    serde_json::to_string(&Layout { nodes, edges }).unwrap()
+}
+
+/// Graphviz representation of the graph, nodes keep their computed positions
+pub fn to_dot(g: &G) -> String {
+    let mut res = String::from("digraph {\n");
+    for ni in g.g.node_indices() {
+        let node = &g.g[ni];
+        let pos = node.location();
+        writeln!(
+            res,
+            "  {} [label=\"{}\", pos=\"{},{}!\"];",
+            ni.index(),
+            node.payload().name.replace('\\', "\\\\").replace('"', "\\\""),
+            pos.x,
+            -pos.y
+        )
+        .unwrap();
+    }
+    for ei in g.g.edge_indices() {
+        let (start, end) = g.g.edge_endpoints(ei).unwrap();
+        writeln!(
+            res,
+            "  {} -> {} [weight={}];",
+            start.index(),
+            end.index(),
+            g.g[ei].payload().weight
+        )
+        .unwrap();
+    }
+    res.push_str("}\n");
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_round_trip() {
+        let raw = serde_json::json!([
+            { "name": "a", "references": [], "constCategory": "Definition", "constType": "Type" },
+            { "name": "b", "references": ["a"], "constCategory": "Theorem", "constType": "Prop" },
+        ]);
+        let (g, _) = crate::load_graph(raw.to_string(), false).unwrap();
+
+        let layout = serde_json::from_str::<Layout>(&export_layout(&g)).unwrap();
+        assert_eq!(layout.nodes.len(), 2);
+        for node in &layout.nodes {
+            let ni = g.g.node_indices().find(|&ni| g.g[ni].payload().name == node.name).unwrap();
+            assert_eq!((node.x, node.y), (g.g[ni].location().x, g.g[ni].location().y));
+            assert!(node.color.iter().all(|c| (0. ..=1.).contains(c)));
+        }
+        let names = layout.nodes.iter().map(|node| node.name.as_str()).collect::<Vec<_>>();
+        let [edge] = layout.edges.as_slice() else {
+            panic!("expected a single edge, got {:?}", layout.edges);
+        };
+        assert_eq!((names[edge.source], names[edge.target], edge.weight), ("a", "b", 1));
+    }
+
+    #[test]
+    fn dot_labels_are_escaped() {
+        let raw = serde_json::json!([
+            { "name": "a\\\"b", "references": [], "constCategory": "Definition", "constType": "Type" },
+        ]);
+        let (g, _) = crate::load_graph(raw.to_string(), false).unwrap();
+
+        let dot = to_dot(&g);
+        assert!(dot.contains(r#"[label="a\\\"b","#), "{dot}");
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use egui::Vec2;
+use petgraph::{graph::NodeIndex, visit::EdgeRef, Direction};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{ForceSettings, G};
+
+pub const MIN_SPEED_LIMIT: f32 = 10.;
+pub const MAX_SPEED_LIMIT: f32 = 100000.;
+
+/// How the pull of an edge grows with its length
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttractionMode {
+    #[default]
+    Quadratic,
+    Linear,
+    Logarithmic,
+}
+
+impl AttractionMode {
+    pub const ALL: [AttractionMode; 3] = [AttractionMode::Quadratic, AttractionMode::Linear, AttractionMode::Logarithmic];
+
+    /// Acceleration along an edge of length `dis`, all modes agree at distance `scale`
+    fn acceleration(self, e_force: f32, dis: f32, scale: f32) -> f32 {
+        match self {
+            AttractionMode::Quadratic => e_force * dis * dis,
+            AttractionMode::Linear => e_force * scale * dis,
+            AttractionMode::Logarithmic => e_force * scale * scale * (dis / scale).ln_1p() / 2f32.ln(),
+        }
+    }
+}
+
+/// How the push between two nodes decays until it vanishes at the repulsion size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RepulsionMode {
+    #[default]
+    Linear,
+    InverseSquare,
+    Gaussian,
+}
+
+impl RepulsionMode {
+    pub const ALL: [RepulsionMode; 3] = [RepulsionMode::Linear, RepulsionMode::InverseSquare, RepulsionMode::Gaussian];
+
+    /// Magnitude of the push at distance `dis`, every mode reaches zero at `r_size`
+    fn acceleration(self, r_force: f32, dis: f32, r_size: f32) -> f32 {
+        match self {
+            RepulsionMode::Linear => r_force * (r_size - dis),
+            RepulsionMode::InverseSquare => {
+                // soft cap, so that overlapping nodes don't fly apart
+                let dis = dis.max(r_size / 4.);
+                r_force * r_size / 6. * ((r_size / dis).powi(2) - 1.)
+            }
+            RepulsionMode::Gaussian => {
+                let sigma = r_size / 2.;
+                r_force * r_size * ((-(dis / sigma).powi(2)).exp() - (-(r_size / sigma).powi(2)).exp())
+            }
+        }
+    }
+}
+
+/// Advances the layout by one step of length `dt`, nodes in `frozen` are held in place
+pub fn simulate_force_graph(g: &mut G, settings: &ForceSettings, frozen: &HashSet<NodeIndex<u32>>, dt: f32) {
+    let mut indices = g.g.node_indices().collect::<Vec<_>>();
+    if indices.len() == 0 { return };
+
+    let neighbors = indices
+        .iter()
+        .map(|&ind| {
+            let mut neigh = g.g.edges(ind).map(|e| (e.target(), e.weight().payload().weight)).collect::<Vec<_>>();
+            if settings.symmetric_attraction {
+                neigh.extend(g.g.edges_directed(ind, Direction::Incoming).map(|e| (e.source(), e.weight().payload().weight)));
+            }
+            (ind, neigh)
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Simulate edge attraction
+    for &ni in &indices {
+        let mut cvel = g.g[ni].payload().vel;
+        for &(oni, weight) in &neighbors[&ni] {
+            let pos = g.node(ni).unwrap().location();
+            let opos = g.node(oni).unwrap().location();
+
+            let dir = opos - pos;
+            let dis = dir.length();
+            let dir = dir.normalized();
+
+
+            let eacc = settings.attraction_mode.acceleration(settings.e_force, dis, settings.r_size) * weight as f32;
+
+            let mr = attraction_mass_ratio(
+                g.g[ni].payload().mass(settings.mass_exponent),
+                g.g[oni].payload().mass(settings.mass_exponent),
+                settings.hub_attraction_cap,
+            );
+
+            let tot_acc = mr * eacc;
+
+            cvel += tot_acc * dt * dir;
+        }
+
+        g.node_mut(ni).unwrap().payload_mut().vel = cvel;
+    }
+
+    // Simulate repulsion
+    // Create a sliding range of size RANGE_SIZE, over the nodes
+    indices.sort_by(|&ni1, &ni2| g.g[ni1].props().location.x.partial_cmp(&g.g[ni2].props().location.x).unwrap());
+    let mut bh = BinaryHeap::<Reverse<(i64, NodeIndex<u32>)>>::new();
+    for &ni in &indices {
+        let pos = g.g[ni].location();
+        while let Some(Reverse((x, oni))) = bh.pop() {
+            if pos.x as i64 - x <= settings.r_size as i64 {
+                bh.push(Reverse((x, oni)));
+                break;
+            }
+        }
+
+        for &Reverse((_, oni)) in &bh {
+            let opos = g.g[oni].location();
+
+            let dir = opos - pos;
+            let dis = dir.length();
+            let dir = dir.normalized();
+
+            if dis > settings.r_size {
+                continue;
+            }
+
+            let racc = -settings.repulsion_mode.acceleration(settings.r_force, dis, settings.r_size);
+            let mr = g.g[oni].payload().mass(settings.mass_exponent) / g.g[ni].payload().mass(settings.mass_exponent);
+
+            let racc_dt = racc*dt;
+
+            g.g[ni].payload_mut().vel += mr * racc_dt * dir;
+            g.g[oni].payload_mut().vel += (1./mr) * racc_dt * (-dir);
+        }
+
+        bh.push(Reverse((pos.x as i64, ni)));
+    }
+
+    // Apply bounding force
+    let center_of_mass = center_of_mass(g, settings, &indices);
+    for &ni in &indices {
+        let dir =  center_of_mass - g.g[ni].location().to_vec2();
+        let dis = dir.length();
+        let dir = dir.normalized();
+
+        let bacc = dis*settings.b_force;
+        g.g[ni].payload_mut().vel += bacc * dt * dir;
+    }
+
+    for &ni in &indices {
+        if frozen.contains(&ni) {
+            g.node_mut(ni).unwrap().payload_mut().vel = Vec2::ZERO;
+            continue;
+        }
+        let mut cvel = g.g[ni].payload().vel;
+        cvel = cvel * (1. - (settings.stiffness));
+        let speed_limit = settings.speed_limit.clamp(MIN_SPEED_LIMIT, MAX_SPEED_LIMIT);
+        cvel = if cvel.length() > speed_limit {cvel.normalized()*speed_limit} else {cvel};
+        let pos = g.g[ni].location();
+        g.node_mut(ni).unwrap().payload_mut().vel = cvel;
+        g.node_mut(ni).unwrap().set_location(pos + cvel * dt);
+    }
+}
+
+/// How much stronger a node of mass `mass` is pulled towards a neighbor of mass `other_mass`, at most `cap`
+fn attraction_mass_ratio(mass: f32, other_mass: f32, cap: Option<f32>) -> f32 {
+    let mr = other_mass / mass;
+    cap.map_or(mr, |cap| mr.min(cap))
+}
+
+/// Kicks every node away from the center of mass, proportionally to its distance from it
+pub fn explode(g: &mut G, settings: &ForceSettings, frozen: &HashSet<NodeIndex<u32>>) {
+    let indices = g.g.node_indices().collect::<Vec<_>>();
+    if indices.is_empty() { return };
+
+    let center_of_mass = center_of_mass(g, settings, &indices);
+    for &ni in &indices {
+        if frozen.contains(&ni) {
+            continue;
+        }
+        // a random part separates nodes lying on top of each other
+        let angle = random::<f32>() * std::f32::consts::TAU;
+        let kick = Vec2::angled(angle) * settings.r_size * random::<f32>();
+        let dir = g.g[ni].location().to_vec2() - center_of_mass + kick;
+        g.g[ni].payload_mut().vel += dir * settings.explode_strength;
+    }
+}
+
+fn center_of_mass(g: &G, settings: &ForceSettings, indices: &[NodeIndex<u32>]) -> Vec2 {
+    let mut center_of_mass = (Vec2::ZERO, 0.);
+
+    for &ni in indices {
+        let mass = g.g[ni].payload().mass(settings.mass_exponent);
+        let loc = g.g[ni].location().to_vec2();
+        let tot_mass = center_of_mass.1 + mass;
+        center_of_mass.0 = (center_of_mass.1 * center_of_mass.0 + mass * loc) / tot_mass;
+        center_of_mass.1 = tot_mass;
+    }
+
+    center_of_mass.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attraction_modes_cross_at_scale() {
+        let (e_force, scale) = (0.001, 100.);
+        let at = |dis| AttractionMode::ALL.map(|mode| mode.acceleration(e_force, dis, scale));
+
+        // shorter edges pull harder the slower the growth
+        let [quadratic, linear, logarithmic] = at(scale / 2.);
+        assert!(quadratic < linear && linear < logarithmic);
+
+        // longer edges pull harder the faster the growth
+        let [quadratic, linear, logarithmic] = at(scale * 2.);
+        assert!(quadratic > linear && linear > logarithmic);
+
+        let [quadratic, linear, logarithmic] = at(scale);
+        assert!((quadratic - linear).abs() < 1e-4 && (linear - logarithmic).abs() < 1e-4);
+    }
+
+    #[test]
+    fn repulsion_vanishes_at_repulsion_size() {
+        let (r_force, r_size) = (2., 400.);
+        for mode in RepulsionMode::ALL {
+            assert!(mode.acceleration(r_force, r_size, r_size).abs() < 1e-3, "{mode:?} doesn't vanish at r_size");
+            // no jump just inside of the range
+            let inside = mode.acceleration(r_force, r_size - 0.01, r_size);
+            assert!(inside >= 0. && inside < 1e-1, "{mode:?} jumps to {inside} inside of r_size");
+        }
+    }
+
+    #[test]
+    fn hub_attraction_cap_limits_mass_ratio() {
+        assert_eq!(attraction_mass_ratio(1., 10., None), 10.);
+        assert_eq!(attraction_mass_ratio(1., 10., Some(3.)), 3.);
+        // pulls towards lighter nodes are left alone
+        assert_eq!(attraction_mass_ratio(10., 1., Some(3.)), 0.1);
+    }
+}
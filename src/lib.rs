@@ -1,31 +1,131 @@
+mod camera;
+mod diff;
 mod edge_shape;
+mod export;
+mod force;
 mod node_shape;
-
-use edge_shape::EdgeShape;
-use node_shape::NodeShape;
+mod palette;
+mod table;
+mod undo;
+
+use camera::{fit_to_rect, CameraTween};
+pub use diff::DiffStatus;
+use diff::{clear_diff, diff_graph, GraphDiff};
+pub use edge_shape::EdgeShape;
+pub use export::{export_layout, to_dot, Layout, LayoutEdge, LayoutNode};
+pub use force::{AttractionMode, RepulsionMode};
+pub use node_shape::NodeShape;
+use node_shape::NodeShapeKind;
+use palette::{Command, CommandPalette, PaletteItem};
+use table::NodeTable;
+use undo::{NodeState, UndoSettings, UndoStack};
 use rfd::AsyncFileDialog;
 
-const STATIC_JSON_FILES: [&str; 7] = ["Nat.zero_add.json", "Nat.prime_of_coprime.json", "Topology.json", "Cardinal.cantor.json", "Continuous.deriv_integral.json", "fermatLastTheoremFour.json", "PFR_conjecture.json"];
+const STATIC_JSON_FILES: [&str; 7] = ["Nat.zero_add.json", "Nat.prime_of_coprime.json", "Topology.json.gz", "Cardinal.cantor.json", "Continuous.deriv_integral.json.gz", "fermatLastTheoremFour.json.gz", "PFR_conjecture.json.gz"];
 pub const SERVER_ADDR: &str = "https://lean-graph.com";
+/// Radius of a node of unit size
+const NODE_RADIUS: f32 = 10.;
+/// How long the view follows the layout after loading a graph
+const FIT_AFTER_LOAD: Duration = Duration::from_secs(1);
+const AUTOSAVE_KEY: &str = "autosave";
+/// Storage key of the key of the last autosaved session, which is offered for restoring on startup
+const LAST_SESSION_KEY: &str = "autosave/last";
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_HISTORY: usize = 30;
+/// Screen space left around the graph when fitting it to the screen
+const FIT_PADDING: f32 = 20.;
+/// Max out-degree set by the aggressive filter offered for large graphs
+const AGGRESSIVE_EDGE_CNT_FILTER: usize = 3;
 
 use std::{
-    collections::{BTreeMap, HashMap, BinaryHeap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     future::Future,
+    io::Read,
     sync::{Arc, RwLock},
-    time::Duration, f32::consts::PI, cmp::Reverse,
+    time::Duration, f32::consts::PI,
 };
 
 use eframe::{App, CreationContext};
-use egui::{Color32, Pos2, Slider, Vec2, Visuals, Hyperlink};
-use egui_graphs::{Edge, GraphView, Node, SettingsInteraction, SettingsNavigation, SettingsStyle, Graph};
-use petgraph::{stable_graph::StableGraph, graph::NodeIndex, EdgeType};
-use rand::random;
-use serde::{Deserialize, Serialize};
+use flate2::read::GzDecoder;
+use egui::{Color32, Pos2, Slider, Vec2, Visuals, Hyperlink, Key, Modifiers};
+use egui_graphs::{DisplayNode, Edge, GraphView, Metadata, Node, SettingsInteraction, SettingsNavigation, SettingsStyle, Graph};
+use petgraph::{algo::tarjan_scc, stable_graph::StableGraph, graph::NodeIndex, Direction, EdgeType};
+use rand::{random, rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Deserializer, Serialize};
 
 pub fn now() -> std::time::Duration {
     std::time::Duration::from_millis(chrono::Local::now().timestamp_millis() as u64)
 }
 
+/// Perceptually uniform colormap similar to viridis, `t` is in 0..=1
+fn viridis(t: f32) -> Color32 {
+    const STOPS: [[f32; 3]; 5] = [
+        [68., 1., 84.],
+        [59., 82., 139.],
+        [33., 145., 140.],
+        [94., 201., 98.],
+        [253., 231., 37.],
+    ];
+    let t = t.clamp(0., 1.) * (STOPS.len() - 1) as f32;
+    let i = (t as usize).min(STOPS.len() - 2);
+    let f = t - i as f32;
+    let c = [0, 1, 2].map(|k| (STOPS[i][k] + (STOPS[i + 1][k] - STOPS[i][k]) * f) as u8);
+    Color32::from_rgb(c[0], c[1], c[2])
+}
+
+fn toggle_dark_mode(ctx: &egui::Context) {
+    if ctx.style().visuals.dark_mode {
+        ctx.set_visuals(Visuals::light());
+    } else {
+        ctx.set_visuals(Visuals::dark());
+    }
+}
+
+/// Displayed color of a node color, in dark mode the lightness is flipped while hue and saturation are kept
+fn theme_color(c: [f32; 3], dark_mode: bool) -> Color32 {
+    if dark_mode {
+        let [h, s, l] = rgb_to_hsl(c);
+        col_ft(hsl_to_rgb([h, s, 1. - l]))
+    } else {
+        col_ft(c.map(|x| x.sqrt()))
+    }
+}
+
+fn rgb_to_hsl([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.;
+    let d = max - min;
+    if d == 0. {
+        return [0., 0., l];
+    }
+    let s = d / (1. - (2. * l - 1.).abs());
+    let h = if max == r {
+        ((g - b) / d).rem_euclid(6.)
+    } else if max == g {
+        (b - r) / d + 2.
+    } else {
+        (r - g) / d + 4.
+    };
+    [h / 6., s, l]
+}
+
+fn hsl_to_rgb([h, s, l]: [f32; 3]) -> [f32; 3] {
+    let c = (1. - (2. * l - 1.).abs()) * s;
+    let h = h * 6.;
+    let x = c * (1. - (h.rem_euclid(2.) - 1.).abs());
+    let [r, g, b] = match h as usize {
+        0 => [c, x, 0.],
+        1 => [x, c, 0.],
+        2 => [0., c, x],
+        3 => [0., x, c],
+        4 => [x, 0., c],
+        _ => [c, 0., x],
+    };
+    let m = l - c / 2.;
+    [r + m, g + m, b + m].map(|v| v.clamp(0., 1.))
+}
+
 fn col_ft(c: [f32; 3]) -> Color32 {
     Color32::from_rgb(
         (c[0] * 256.) as u8,
@@ -43,36 +143,109 @@ impl EdgeType for Directed {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd, Ord, Eq)]
-enum ConstCategory {
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Hash)]
+pub enum ConstCategory {
     Theorem,
     Definition,
     Axiom,
     Other,
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 struct NodeData {
     name: String,
     references: Vec<String>,
     const_category: ConstCategory,
-    const_type: String
+    const_type: String,
+    #[serde(default)]
+    source_file: Option<String>,
+    #[serde(default)]
+    source_line: Option<u32>,
+    /// Base url of the repository the source file is in, e.g. `https://github.com/owner/repo/blob/master`
+    #[serde(default)]
+    source_url: Option<String>,
+}
+
+impl NodeData {
+    /// Stands in for a referenced constant that wasn't extracted
+    fn placeholder(name: String) -> Self {
+        Self {
+            name,
+            references: vec![],
+            const_category: ConstCategory::Other,
+            const_type: "(not extracted)".into(),
+            source_file: None,
+            source_line: None,
+            source_url: None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-struct NodePayload {
-    name: String,
+pub struct NodePayload {
+    pub name: String,
     vel: Vec2,
     color: [f32; 3],
     comp_color: ([f32; 3], f32),
-    const_category: ConstCategory,
-    size: f32,
-    const_type: String
+    pub const_category: ConstCategory,
+    pub size: f32,
+    /// Size after applying the display settings, used for drawing and physics
+    #[serde(default)]
+    display_size: f32,
+    /// Whether the label is drawn regardless of the on-screen size
+    #[serde(default)]
+    always_label: bool,
+    /// Whether the node is outside of the highlighted set
+    #[serde(default)]
+    dimmed: bool,
+    /// Pinned nodes are not moved by the simulation
+    #[serde(default)]
+    pinned: bool,
+    /// Manually chosen location, which survives filter changes and reloads
+    #[serde(default)]
+    fixed_location: Option<Pos2>,
+    /// Manually colored nodes keep their color, both when randomizing and in propagation
+    #[serde(default)]
+    color_locked: bool,
+    /// Nothing in the filtered graph depends on the node
+    #[serde(default)]
+    is_root: bool,
+    /// The node depends on nothing in the filtered graph
+    #[serde(default)]
+    is_leaf: bool,
+    /// Index of the detected dependency cycle the node is part of
+    #[serde(default)]
+    cycle: Option<usize>,
+    /// Position on the heatmap colormap, overrides the color when set
+    #[serde(default)]
+    heat: Option<f32>,
+    /// Status of the node in the last comparison with another extraction
+    #[serde(default)]
+    diff: Option<DiffStatus>,
+    /// Namespace of a node standing in for all its constants
+    #[serde(default)]
+    group: Option<String>,
+    /// Number of merged extractions the constant appeared in
+    #[serde(default = "NodePayload::default_source_count")]
+    source_count: u32,
+    pub const_type: String,
+    #[serde(default)]
+    source_file: Option<String>,
+    #[serde(default)]
+    source_line: Option<u32>,
+    #[serde(default)]
+    source_url: Option<String>,
+}
+
+fn random_node_color(rng: &mut impl Rng) -> [f32; 3] {
+    [0.; 3].map(|_| (rng.gen::<f32>() / 3.)*2.)
 }
 
-fn random_node_color() -> [f32; 3] {
-    [0.; 3].map(|_| (random::<f32>() / 3.)*2.)
+/// `count` node colors determined by `seed`
+fn seeded_colors(seed: u64, count: usize) -> Vec<[f32; 3]> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..count).map(|_| random_node_color(&mut rng)).collect()
 }
 
 impl From<&NodeData> for NodePayload {
@@ -80,33 +253,133 @@ impl From<&NodeData> for NodePayload {
         Self {
             name: value.name.clone(),
             const_category: value.const_category.clone(),
-            color: random_node_color(),
+            color: random_node_color(&mut rand::thread_rng()),
             comp_color: Default::default(),
             vel: Vec2::ZERO,
             size: ((value.references.len() + 1) as f32).sqrt(),
-            const_type: value.const_type.clone()
+            display_size: ((value.references.len() + 1) as f32).sqrt(),
+            always_label: false,
+            dimmed: false,
+            pinned: false,
+            fixed_location: None,
+            color_locked: false,
+            is_root: false,
+            is_leaf: false,
+            heat: None,
+            diff: None,
+            group: None,
+            source_count: 1,
+            cycle: None,
+            const_type: value.const_type.clone(),
+            source_file: value.source_file.clone(),
+            source_line: value.source_line,
+            source_url: value.source_url.clone(),
         }
     }
 }
 
 impl NodePayload {
+    fn default_source_count() -> u32 {
+        1
+    }
     pub fn comp_color(&self) -> [f32; 3] {
+        // nodes not reached by the coloring yet keep their own color
+        if self.comp_color.1 == 0. {
+            return self.color;
+        }
         self.comp_color.0.map(|x| x / self.comp_color.1)
     }
-    pub fn mass(&self) -> f32 {
-        self.size
+    pub fn mass(&self, exponent: f32) -> f32 {
+        self.display_size.powf(exponent)
+    }
+    /// Location of the definition formatted as `file:line`
+    pub fn source_location(&self) -> Option<String> {
+        let file = self.source_file.as_ref()?;
+        Some(match self.source_line {
+            Some(line) => format!("{file}:{line}"),
+            None => file.clone(),
+        })
+    }
+    /// Link to the definition in the repository, if the repository url is known
+    pub fn source_link(&self) -> Option<String> {
+        let base = self.source_url.as_ref()?;
+        let file = self.source_file.as_ref()?;
+        Some(match self.source_line {
+            Some(line) => format!("{}/{file}#L{line}", base.trim_end_matches('/')),
+            None => format!("{}/{file}", base.trim_end_matches('/')),
+        })
+    }
+}
+
+/// Edges go from the referenced constant to the one referencing it, so the outgoing neighbors of a node are its dependents
+#[derive(Clone, Debug, Serialize)]
+pub struct EdgePayload {
+    /// Number of times the source is referenced by the target
+    pub weight: u32,
+    /// Status of the edge in the last comparison with another extraction
+    pub diff: Option<DiffStatus>,
+}
+
+impl<'de> Deserialize<'de> for EdgePayload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Fields {
+            weight: u32,
+            #[serde(default)]
+            diff: Option<DiffStatus>,
+        }
+
+        // visualizations stored before edges were weighted have a null payload
+        Ok(match Option::<Fields>::deserialize(deserializer)? {
+            Some(Fields { weight, diff }) => Self { weight, diff },
+            None => Self { weight: 1, diff: None },
+        })
     }
 }
 
-type G = egui_graphs::Graph<NodePayload, (), Directed, u32, NodeShape, EdgeShape>;
+/// Change of a node made from its selection window
+enum NodeEdit {
+    Pinned(bool),
+    Fixed(bool),
+    Color([f32; 3]),
+    ColorLocked(bool),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HighlightDirection {
+    None,
+    /// Everything the selection transitively depends on
+    Dependencies,
+    /// Everything that transitively depends on the selection
+    Dependents,
+}
+
+pub type G = egui_graphs::Graph<NodePayload, EdgePayload, Directed, u32, NodeShape, EdgeShape>;
 
 #[derive(Serialize, Deserialize, Clone)]
-struct ForceSettings {
-    r_force: f32,
-    r_size: f32,
-    e_force: f32,
-    b_force: f32,
-    stiffness: f32,
+#[serde(default)]
+pub struct ForceSettings {
+    pub r_force: f32,
+    pub r_size: f32,
+    pub e_force: f32,
+    pub b_force: f32,
+    pub stiffness: f32,
+    /// Length of a single simulation step in seconds
+    pub fixed_dt: f32,
+    /// Maximal number of simulation steps per frame
+    pub max_substeps: usize,
+    /// Mass of a node is its size raised to this exponent
+    pub mass_exponent: f32,
+    /// Pull nodes along incoming edges too, not only outgoing ones
+    pub symmetric_attraction: bool,
+    pub attraction_mode: AttractionMode,
+    pub repulsion_mode: RepulsionMode,
+    /// Maximal ratio of masses applied to the attraction towards a heavier node, `None` means no cap
+    pub hub_attraction_cap: Option<f32>,
+    /// Maximal speed of a node in canvas units per second
+    pub speed_limit: f32,
+    /// Velocity given to nodes per unit of distance from the center when exploding the layout
+    pub explode_strength: f32,
 }
 
 impl Default for ForceSettings {
@@ -116,26 +389,71 @@ impl Default for ForceSettings {
             e_force: 0.001,
             b_force: 0.05,
             stiffness: 0.5,
-            r_size: 200.
+            r_size: 200.,
+            fixed_dt: 1. / 120.,
+            max_substeps: 8,
+            mass_exponent: 1.,
+            symmetric_attraction: false,
+            attraction_mode: AttractionMode::Quadratic,
+            repulsion_mode: RepulsionMode::Linear,
+            hub_attraction_cap: None,
+            speed_limit: 10000.,
+            explode_strength: 60.,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+enum ColoringMode {
+    /// Colors flow from dependencies to the constants using them
+    #[default]
+    Propagation,
+    /// Every connected component gets a single color
+    Component,
+    /// Number of constants referencing the node on a colormap
+    Heatmap,
+}
+
+impl ColoringMode {
+    const ALL: [ColoringMode; 3] = [ColoringMode::Propagation, ColoringMode::Component, ColoringMode::Heatmap];
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 struct ColoringSettings {
+    mode: ColoringMode,
     color_loss: f32,
+    /// Ignore back edges of dependency cycles when propagating colors
+    break_cycles: bool,
+    /// Seed of the random node colors
+    color_seed: u64,
+    /// Randomize the colors of nodes hidden by the filters too
+    randomize_hidden: bool,
 }
 
 impl Default for ColoringSettings {
     fn default() -> Self {
-        Self { color_loss: 0.5 }
+        Self { mode: ColoringMode::Propagation, color_loss: 0.5, break_cycles: false, color_seed: 0, randomize_hidden: false }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 struct FilterSettings {
     node_type_filter: BTreeMap<ConstCategory, bool>,
     outer_edge_cnt_filter: usize,
+    /// Nodes whose neighborhood is shown instead of the filtered graph
+    focus: Option<Vec<NodeIndex<u32>>>,
+    focus_hops: usize,
+    /// Only show constants whose type contains this text, ignoring case
+    type_contains: Option<String>,
+    /// Only the connected components containing these nodes are shown, after the other filters
+    isolate: Option<Vec<NodeIndex<u32>>>,
+    /// Hide constants without any references in either direction
+    hide_isolated: bool,
+    /// Number of namespace components nodes are grouped by, 0 disables grouping
+    group_depth: usize,
+    expanded_groups: BTreeSet<String>,
 }
 
 impl Default for FilterSettings {
@@ -149,17 +467,258 @@ impl Default for FilterSettings {
 
         Self {
             node_type_filter,
-            outer_edge_cnt_filter: 10
+            outer_edge_cnt_filter: 10,
+            focus: None,
+            focus_hops: 2,
+            type_contains: None,
+            hide_isolated: false,
+            isolate: None,
+            group_depth: 0,
+            expanded_groups: BTreeSet::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+struct DisplaySettings {
+    node_size_scale: f32,
+    node_size_min: f32,
+    node_size_max: f32,
+    /// Minimal on-screen radius of a node for its label to be drawn
+    label_min_radius: f32,
+    /// Number of largest nodes whose labels are always drawn
+    label_top_n: usize,
+    edge_alpha_dark: u8,
+    edge_alpha_light: u8,
+    /// Alpha of edges pointing to a selected node
+    edge_alpha_selected: u8,
+    node_shapes: BTreeMap<ConstCategory, NodeShapeKind>,
+    highlight_roots_leaves: bool,
+    /// Outline constants that appeared in more than one merged extraction
+    highlight_duplicates: bool,
+    /// Color nodes and edges by the result of the comparison with another extraction
+    show_diff: bool,
+    /// Animate dots along the edges instead of drawing arrow tips
+    edge_flow: bool,
+    /// Above this edge count only edges of selected nodes are animated
+    edge_flow_max_edges: usize,
+    #[serde(skip)]
+    large_graph: bool,
+    /// Node under the pointer in the last frame, its edges are animated even in large graphs
+    #[serde(skip)]
+    hovered: Option<NodeIndex<u32>>,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            node_size_scale: 1.,
+            node_size_min: 5.,
+            node_size_max: 500.,
+            label_min_radius: 8.,
+            label_top_n: 10,
+            edge_alpha_dark: 50,
+            edge_alpha_light: 180,
+            edge_alpha_selected: 230,
+            node_shapes: BTreeMap::from([
+                (ConstCategory::Theorem, NodeShapeKind::Pentagon),
+                (ConstCategory::Definition, NodeShapeKind::Triangle),
+                (ConstCategory::Axiom, NodeShapeKind::Circle),
+                (ConstCategory::Other, NodeShapeKind::Square),
+            ]),
+            highlight_roots_leaves: false,
+            highlight_duplicates: false,
+            show_diff: false,
+            edge_flow: false,
+            edge_flow_max_edges: 2000,
+            large_graph: false,
+            hovered: None,
         }
     }
 }
 
+impl DisplaySettings {
+    /// Radius of the node after scaling and clamping
+    fn node_radius(&self, size: f32) -> f32 {
+        (NODE_RADIUS * size * self.node_size_scale).clamp(self.node_size_min, self.node_size_max.max(self.node_size_min))
+    }
+    fn id() -> egui::Id {
+        egui::Id::new("display_settings")
+    }
+    /// Makes the settings available to the node and edge shapes, shared so that drawing does not copy them per shape
+    fn store_into_ctx(&self, ctx: &egui::Context) {
+        ctx.data_mut(|d| d.insert_temp(Self::id(), Arc::new(self.clone())));
+    }
+    fn load_from_ctx(ctx: &egui::Context) -> Arc<Self> {
+        ctx.data(|d| d.get_temp::<Arc<Self>>(Self::id())).unwrap_or_default()
+    }
+}
+
+/// Inputs of the color propagation that change frame to frame
+#[derive(PartialEq)]
+struct ColoringSignature {
+    selected: Vec<NodeIndex<u32>>,
+    color_loss: f32,
+}
+
+/// Graph structure needed for color propagation, only rebuilt when the filtered graph changes
+struct ColoringCache {
+    topo_sort: Vec<NodeIndex<u32>>,
+    rev_neighbors: HashMap<NodeIndex<u32>, Vec<NodeIndex<u32>>>,
+    filter_settings: FilterSettings,
+    break_cycles: bool,
+    mode: ColoringMode,
+    signature: Option<ColoringSignature>,
+}
+
+/// State of a namespace group carried over the rebuilds of the filtered graph
+#[derive(Clone, Copy)]
+struct GroupState {
+    location: Pos2,
+    vel: Vec2,
+    comp_color: ([f32; 3], f32),
+    heat: Option<f32>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct StoredData {
     g: G,
     force_settings: ForceSettings,
     filter_settings: FilterSettings,
-    coloring_settings: ColoringSettings
+    coloring_settings: ColoringSettings,
+    #[serde(default)]
+    display_settings: DisplaySettings,
+}
+
+/// Autosaved part of a visualization, only the state of the nodes unlike [`StoredData`], so it fits the browser storage
+#[derive(Serialize, Deserialize)]
+struct Session {
+    /// States of the nodes by constant name
+    nodes: HashMap<String, SessionNode>,
+    force_settings: ForceSettings,
+    filter_settings: FilterSettings,
+    coloring_settings: ColoringSettings,
+    display_settings: DisplaySettings,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionNode {
+    location: Pos2,
+    color: [f32; 3],
+    color_locked: bool,
+    pinned: bool,
+    fixed_location: Option<Pos2>,
+}
+
+impl SessionNode {
+    fn of(payload: &NodePayload, location: Pos2) -> Self {
+        Self {
+            location,
+            color: payload.color,
+            color_locked: payload.color_locked,
+            pinned: payload.pinned,
+            fixed_location: payload.fixed_location,
+        }
+    }
+    fn apply(&self, payload: &mut NodePayload) {
+        payload.color = self.color;
+        payload.color_locked = self.color_locked;
+        payload.pinned = self.pinned;
+        payload.fixed_location = self.fixed_location;
+    }
+}
+
+impl Session {
+    /// Applies the saved states to the nodes `nis` of `g`
+    fn apply_nodes(&self, g: &mut G, nis: impl IntoIterator<Item = NodeIndex<u32>>) {
+        for ni in nis {
+            let Some(state) = self.nodes.get(&g.g[ni].payload().name) else {
+                continue;
+            };
+            state.apply(g.g[ni].payload_mut());
+            g.g[ni].set_location(state.location);
+        }
+    }
+}
+
+/// Autosaved session from a previous run together with what's needed to show its graph again
+struct PendingSession {
+    key: String,
+    session: Session,
+    /// Extracted data of the graph, see [`graph_source`]
+    source: Option<String>,
+}
+
+/// Storage key of the autosaved session of the graph, graphs with the same constants share it
+fn session_key(g: &G) -> String {
+    let mut names = g.g.node_weights().map(|node| node.payload().name.as_str()).collect::<Vec<_>>();
+    names.sort_unstable();
+    // FNV-1a, unlike the std hashers it gives the same keys across Rust releases
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in names.iter().flat_map(|name| name.bytes().chain([0])) {
+        hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{AUTOSAVE_KEY}/{hash:016x}")
+}
+
+/// Storage key of the extracted data of the graph of the session stored under `key`
+fn source_key(key: &str) -> String {
+    format!("{key}/source")
+}
+
+/// Extracted data the graph can be loaded from again
+fn graph_source(g: &G) -> String {
+    let mut references = HashMap::<_, Vec<String>>::new();
+    for ei in g.g.edge_indices() {
+        let (start, end) = g.g.edge_endpoints(ei).unwrap();
+        let name = &g.g[start].payload().name;
+        references.entry(end).or_default().extend(std::iter::repeat(name.clone()).take(g.g[ei].payload().weight as usize));
+    }
+    let nodes = g.g.node_indices().map(|ni| {
+        let payload = g.g[ni].payload();
+        NodeData {
+            name: payload.name.clone(),
+            references: references.remove(&ni).unwrap_or_default(),
+            const_category: payload.const_category.clone(),
+            const_type: payload.const_type.clone(),
+            source_file: payload.source_file.clone(),
+            source_line: payload.source_line,
+            source_url: payload.source_url.clone(),
+        }
+    }).collect::<Vec<_>>();
+    serde_json::to_string(&nodes).unwrap()
+}
+
+fn read_session(storage: &dyn eframe::Storage, key: &str) -> Option<PendingSession> {
+    Some(PendingSession {
+        key: key.to_string(),
+        session: serde_json::from_str(&storage.get_string(key)?).ok()?,
+        source: storage.get_string(&source_key(key)).filter(|source| !source.is_empty()),
+    })
+}
+
+/// Drops the session when the page is left normally, the web app doesn't see the close request
+#[cfg(target_arch = "wasm32")]
+fn clear_session_on_pagehide() {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let on_pagehide = Closure::<dyn FnMut()>::new(|| {
+        let Some(storage) = web_sys::window().and_then(|window| window.local_storage().ok().flatten()) else {
+            return;
+        };
+        if let Ok(Some(key)) = storage.get_item(LAST_SESSION_KEY) {
+            if !key.is_empty() {
+                let _ = storage.set_item(&key, "");
+            }
+        }
+    });
+    let _ = window.add_event_listener_with_callback("pagehide", on_pagehide.as_ref().unchecked_ref());
+    // the listener lives as long as the page
+    on_pagehide.forget();
 }
 
 pub struct MApp {
@@ -167,48 +726,150 @@ pub struct MApp {
     g_updated: Arc<RwLock<bool>>,
     fg: G,
     last_update: Duration,
+    /// Elapsed time not yet consumed by simulation steps
+    time_accumulator: f32,
     force_settings: ForceSettings,
     filter_settings: FilterSettings,
     coloring_settings: ColoringSettings,
+    display_settings: DisplaySettings,
+    highlight_direction: HighlightDirection,
+    /// Nodes dragged in the previous frame
+    dragged_nodes: Vec<NodeIndex<u32>>,
+    /// Strongly connected components of size > 1 found by the last cycle detection
+    cycles: Option<Vec<Vec<NodeIndex<u32>>>>,
+    coloring_cache: Option<ColoringCache>,
     data_to_load: Arc<RwLock<Option<StoredData>>>,
     fit_to_screen: Arc<RwLock<bool>>,
+    /// Frame the whole graph including the node sizes on the next frame
+    fit_whole_graph: bool,
+    /// Progress of the graph currently being loaded
+    load_progress: Arc<RwLock<Option<f32>>>,
+    /// Keep fitting the view to the graph until then, so it follows the settling layout
+    fit_until: Option<Duration>,
+    camera_tween: Option<CameraTween>,
+    /// Autosaved session from a previous run, offered for restoring
+    session_to_restore: Option<PendingSession>,
+    /// Storage key of the session of the shown graph
+    session_key: String,
+    /// Look up the session of a newly loaded graph on the next frame
+    session_lookup: bool,
+    /// The user did something since the last autosave
+    session_changed: bool,
+    autosave_failed: bool,
+    /// The window is being closed normally, so the session doesn't need to be kept
+    closing: bool,
+    /// Create placeholder nodes for references to constants missing from the data
+    create_placeholders: bool,
+    load_report: Arc<RwLock<LoadReport>>,
+    show_load_report: bool,
+    undo_stack: UndoStack,
+    /// Location and velocity of namespace group nodes, which are recreated every frame
+    group_states: HashMap<String, GroupState>,
+    /// Recently selected nodes, `history_pos` points at the current one
+    history: VecDeque<NodeIndex<u32>>,
+    history_pos: usize,
+    /// Graphs with more nodes ask before being simulated
+    large_graph_threshold: usize,
+    /// Node count of a just loaded large graph waiting for confirmation
+    large_graph_prompt: Option<usize>,
+    /// Lowest and highest number of references shown by the heatmap
+    heat_range: Option<(usize, usize)>,
+    /// Dragged node and its location in the last frame
+    drag_anchor: Option<(NodeIndex<u32>, Pos2)>,
+    command_palette: CommandPalette,
+    /// Result of the last comparison with another extraction
+    graph_diff: Arc<RwLock<Option<GraphDiff>>>,
+    node_table: NodeTable,
 }
 
 impl MApp {
     pub fn new(ctx: &CreationContext<'_>, default_file_raw: String) -> Self {
-        // setup font that support math characters
-        let mut fonts = egui::FontDefinitions::default();
-        fonts.font_data.insert("noto_sans_math".into(), egui::FontData::from_static(include_bytes!("../static/NotoSansMath-Regular.ttf")));
-        fonts.families.entry(egui::FontFamily::Proportional).or_default().insert(0, "noto_sans_math".into());
-        ctx.egui_ctx.set_fonts(fonts);
-
-        let g = load_graph(default_file_raw);
+        setup_fonts(&ctx.egui_ctx);
 
+        let (g, load_report) = load_graph(default_file_raw, false).unwrap_or_else(|err| {
+            log::error!("failed to parse the default graph: {err}");
+            (G::new(StableGraph::default()), LoadReport::default())
+        });
+        let mut app = Self::with_graph(g, load_report);
+        // the last session may be of another graph than the default one
+        app.session_to_restore = ctx.storage.and_then(|storage| read_session(storage, &storage.get_string(LAST_SESSION_KEY)?));
+        #[cfg(target_arch = "wasm32")]
+        clear_session_on_pagehide();
+        app
+    }
+    fn with_graph(g: G, load_report: LoadReport) -> Self {
+        let session_key = session_key(&g);
         Self {
             g: Arc::new(RwLock::new(g.clone())),
             g_updated: Default::default(),
             last_update: now(),
+            time_accumulator: 0.,
             force_settings: Default::default(),
             fg: g,
             filter_settings: Default::default(),
             coloring_settings: Default::default(),
+            display_settings: Default::default(),
+            highlight_direction: HighlightDirection::None,
+            dragged_nodes: vec![],
+            cycles: None,
+            coloring_cache: None,
             data_to_load: Default::default(),
-            fit_to_screen: Default::default()
+            fit_to_screen: Default::default(),
+            fit_whole_graph: false,
+            load_progress: Default::default(),
+            fit_until: Some(now() + FIT_AFTER_LOAD),
+            camera_tween: None,
+            session_to_restore: None,
+            session_key,
+            session_lookup: false,
+            session_changed: false,
+            autosave_failed: false,
+            closing: false,
+            create_placeholders: false,
+            load_report: Arc::new(RwLock::new(load_report)),
+            show_load_report: false,
+            undo_stack: Default::default(),
+            group_states: HashMap::new(),
+            history: VecDeque::new(),
+            history_pos: 0,
+            large_graph_threshold: 10000,
+            large_graph_prompt: None,
+            heat_range: None,
+            drag_anchor: None,
+            command_palette: Default::default(),
+            graph_diff: Default::default(),
+            node_table: Default::default(),
         }
     }
-    fn color_nodes(&mut self) {
-        let node_indices = self.fg.g.node_indices().collect::<Vec<_>>();
-        for &ni in &node_indices {
-            self.fg.g[ni].payload_mut().comp_color = Default::default();
+    fn update_roots_leaves(&mut self) {
+        for ni in self.fg.g.node_indices().collect::<Vec<_>>() {
+            let is_root = self.fg.g.neighbors_directed(ni, Direction::Outgoing).next().is_none();
+            let is_leaf = self.fg.g.neighbors_directed(ni, Direction::Incoming).next().is_none();
+            let payload = self.fg.g[ni].payload_mut();
+            payload.is_root = is_root;
+            payload.is_leaf = is_leaf;
         }
+    }
+    fn build_coloring_cache(&self) -> ColoringCache {
+        let node_indices = self.fg.g.node_indices().collect::<Vec<_>>();
 
         // get node_indices as topological sort
 
+        let ignored_edges = if self.coloring_settings.break_cycles {
+            back_edges(&self.fg.g)
+        } else {
+            HashSet::new()
+        };
+
         let mut out_degree = HashMap::new();
         let mut rev_neighbors = HashMap::new();
         for &ni in &node_indices {
-            *out_degree.entry(ni).or_insert(0) += self.fg.g.neighbors(ni).count();
+            out_degree.entry(ni).or_insert(0);
             for oni in self.fg.g.neighbors(ni).collect::<Vec<_>>() {
+                if ignored_edges.contains(&(ni, oni)) {
+                    continue;
+                }
+                *out_degree.get_mut(&ni).unwrap() += 1;
                 rev_neighbors.entry(oni).or_insert(vec![]).push(ni);
             }
         }
@@ -232,9 +893,57 @@ impl MApp {
             }
         }
 
+        ColoringCache {
+            topo_sort,
+            rev_neighbors,
+            filter_settings: self.filter_settings.clone(),
+            break_cycles: self.coloring_settings.break_cycles,
+            mode: self.coloring_settings.mode,
+            signature: None,
+        }
+    }
+    fn color_nodes(&mut self) {
+        let cache_valid = self.coloring_cache.as_ref().is_some_and(|cache| {
+            cache.filter_settings == self.filter_settings
+                && cache.break_cycles == self.coloring_settings.break_cycles
+                && cache.mode == self.coloring_settings.mode
+        });
+        let mut cache = match self.coloring_cache.take() {
+            Some(cache) if cache_valid => cache,
+            _ => {
+                self.update_roots_leaves();
+                self.build_coloring_cache()
+            }
+        };
+
+        if self.coloring_settings.mode != ColoringMode::Propagation {
+            if !cache_valid {
+                self.color_by_structure();
+            }
+            self.coloring_cache = Some(cache);
+            return;
+        }
+
+        // skip the propagation if nothing it depends on changed since the last frame
+        let node_indices = self.fg.g.node_indices().collect::<Vec<_>>();
+        let signature = ColoringSignature {
+            selected: node_indices.iter().copied().filter(|&ni| self.fg.g[ni].selected()).collect(),
+            color_loss: self.coloring_settings.color_loss,
+        };
+        if cache.signature.as_ref() == Some(&signature) {
+            self.coloring_cache = Some(cache);
+            return;
+        }
+        cache.signature = Some(signature);
+
+        for &ni in &node_indices {
+            self.fg.g[ni].payload_mut().comp_color = Default::default();
+            self.fg.g[ni].payload_mut().heat = None;
+        }
+
         const SELECTED_MP: f32 = 3.;
 
-        for &ni in &topo_sort {
+        for &ni in &cache.topo_sort {
             let color = self.fg.g.node_weight(ni).unwrap().payload().color;
             let size = self.fg.g[ni].payload().size;
             let size = if self.fg.g[ni].selected() {size*SELECTED_MP} else {size};
@@ -246,10 +955,13 @@ impl MApp {
                 comp_color.0[2] + color[2] * size,
             ];
             self.fg.g[ni].payload_mut().comp_color.1 += size;
+            if self.fg.g[ni].payload().color_locked {
+                self.fg.g[ni].payload_mut().comp_color = (color.map(|x| x * size), size);
+            }
             let comp_color = self.fg.g[ni].payload_mut().comp_color;
 
             // for each neighbor add my own comp color with some loss based on a constant
-            for &oni in &rev_neighbors[&ni] {
+            for &oni in &cache.rev_neighbors[&ni] {
                 for i in 0..3 {
                     self.fg.g[oni].payload_mut().comp_color.0[i] +=
                         comp_color.0[i] * self.coloring_settings.color_loss;
@@ -258,145 +970,682 @@ impl MApp {
                     comp_color.1 * self.coloring_settings.color_loss;
             }
         }
-    }
-    fn simulate_force_graph(&mut self, dt: f32) {
-        let mut indices = self.fg.g.node_indices().collect::<Vec<_>>();
-        if indices.len() == 0 { return };
-
-        let neighbors = indices
-            .iter()
-            .map(|&ind| {
-                let neigh = self.fg.g.neighbors(ind).collect::<Vec<_>>();
-                (ind, neigh)
-            })
-            .collect::<HashMap<_, _>>();
-
-        // Simulate edge attraction
-        for &ni in &indices {
-            let mut cvel = self.fg.g[ni].payload().vel;
-            for &oni in &neighbors[&ni] {
-                let pos = self.fg.node(ni).unwrap().location();
-                let opos = self.fg.node(oni).unwrap().location();
-
-                let dir = opos - pos;
-                let dis = dir.length();
-                let dir = dir.normalized();
-
-
-                let eacc = self.force_settings.e_force * dis * dis;
-
-                let mr = self.fg.g[oni].payload().mass() / self.fg.g[ni].payload().mass();
 
-                let tot_acc = mr * eacc;
-
-                cvel += tot_acc * dt * dir;
+        self.coloring_cache = Some(cache);
+    }
+    /// Colors for the modes computed from the graph structure instead of propagation
+    fn color_by_structure(&mut self) {
+        let node_indices = self.fg.g.node_indices().collect::<Vec<_>>();
+        self.heat_range = None;
+        match self.coloring_settings.mode {
+            ColoringMode::Propagation => {}
+            ColoringMode::Component => {
+                let components = weak_components(&self.fg.g);
+                // the first node of each component decides its color
+                let mut colors = HashMap::new();
+                for &ni in &node_indices {
+                    let color = *colors.entry(components[&ni]).or_insert(self.fg.g[ni].payload().color);
+                    let payload = self.fg.g[ni].payload_mut();
+                    payload.comp_color = (color, 1.);
+                    payload.heat = None;
+                }
             }
-
-            self.fg.node_mut(ni).unwrap().payload_mut().vel = cvel;
+            ColoringMode::Heatmap => {
+                let degrees = node_indices.iter().map(|&ni| self.fg.g.neighbors_directed(ni, Direction::Outgoing).count()).collect::<Vec<_>>();
+                let (Some(&min), Some(&max)) = (degrees.iter().min(), degrees.iter().max()) else {
+                    return;
+                };
+                // log scale, degrees of constants are heavy tailed
+                let (lmin, lmax) = ((min as f32).ln_1p(), (max as f32).ln_1p());
+                for (&ni, &degree) in node_indices.iter().zip(&degrees) {
+                    let heat = if lmax > lmin { ((degree as f32).ln_1p() - lmin) / (lmax - lmin) } else { 0. };
+                    self.fg.g[ni].payload_mut().heat = Some(heat);
+                }
+                self.heat_range = Some((min, max));
+            }
+        }
+    }
+    fn apply_display_settings(&mut self) {
+        let mut node_indices = self.fg.g.node_indices().collect::<Vec<_>>();
+        for &ni in &node_indices {
+            let payload = self.fg.g[ni].payload_mut();
+            payload.display_size = self.display_settings.node_radius(payload.size) / NODE_RADIUS;
+            payload.always_label = false;
         }
 
-        // Simulate repulsion
-        // Create a sliding range of size RANGE_SIZE, over the nodes
-        indices.sort_by(|&ni1, &ni2| self.fg.g[ni1].props().location.x.partial_cmp(&self.fg.g[ni2].props().location.x).unwrap());
-        let mut bh = BinaryHeap::<Reverse<(i64, NodeIndex<u32>)>>::new();
-        for &ni in &indices {
-            let pos = self.fg.g[ni].location();
-            while let Some(Reverse((x, oni))) = bh.pop() {
-                if pos.x as i64 - x <= self.force_settings.r_size as i64 {
-                    bh.push(Reverse((x, oni)));
-                    break;
+        node_indices.sort_by(|&ni1, &ni2| self.fg.g[ni2].payload().size.partial_cmp(&self.fg.g[ni1].payload().size).unwrap());
+        for &ni in node_indices.iter().take(self.display_settings.label_top_n) {
+            self.fg.g[ni].payload_mut().always_label = true;
+        }
+    }
+    fn detect_cycles(&mut self) {
+        let cycles = tarjan_scc(&self.fg.g)
+            .into_iter()
+            .filter(|scc| scc.len() > 1)
+            .collect::<Vec<_>>();
+
+        self.clear_cycles();
+        for (i, scc) in cycles.iter().enumerate() {
+            for &ni in scc {
+                self.fg.g[ni].payload_mut().cycle = Some(i);
+            }
+        }
+        self.cycles = Some(cycles);
+    }
+    fn clear_cycles(&mut self) {
+        // hidden nodes keep their mark in the source graph, clear it there as well
+        let mut g = self.g.write().unwrap();
+        for ni in g.g.node_indices().collect::<Vec<_>>() {
+            g.g[ni].payload_mut().cycle = None;
+        }
+        drop(g);
+        for ni in self.fg.g.node_indices().collect::<Vec<_>>() {
+            self.fg.g[ni].payload_mut().cycle = None;
+        }
+        self.cycles = None;
+    }
+    fn hovered_node(&self, ui: &egui::Ui) -> Option<NodeIndex<u32>> {
+        let pointer_pos = ui.ctx().pointer_hover_pos()?;
+        let meta = Metadata::get(ui);
+        let pos = meta.screen_to_canvas_pos(pointer_pos);
+        // prefer the node drawn last, which is on top
+        self.fg.g.node_indices().filter(|&ni| {
+            DisplayNode::<NodePayload, EdgePayload, Directed, u32>::is_inside(self.fg.g[ni].display(), pos)
+        }).last()
+    }
+    fn draw_hover_tooltip(&self, ui: &egui::Ui) {
+        let Some(ni) = self.hovered_node(ui) else {
+            return;
+        };
+        const MAX_TYPE_LEN: usize = 200;
+
+        let data = self.fg.g[ni].payload();
+        let mut const_type = data.const_type.chars().take(MAX_TYPE_LEN).collect::<String>();
+        if const_type.len() < data.const_type.len() {
+            const_type.push('…');
+        }
+        let dependencies = self.fg.g.neighbors_directed(ni, Direction::Incoming).count();
+        let dependents = self.fg.g.neighbors_directed(ni, Direction::Outgoing).count();
+
+        egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("node_tooltip"), |ui| {
+            ui.strong(data.name.clone());
+            ui.label(format!("{:?}", data.const_category));
+            ui.label(const_type);
+            ui.label(format!("References {dependencies}, referenced by {dependents}"));
+        });
+    }
+    fn draw_load_report_window(&mut self, ctx: &egui::Context) {
+        let report = self.load_report.read().unwrap();
+        egui::Window::new("Load report").open(&mut self.show_load_report).show(ctx, |ui| {
+            ui.label(format!("{} references could not be resolved", report.unresolved.len()));
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (source, target) in &report.unresolved {
+                    ui.label(format!("{source} → {target}"));
+                }
+            });
+        });
+    }
+    fn draw_large_graph_window(&mut self, ctx: &egui::Context) {
+        let Some(node_cnt) = self.large_graph_prompt else {
+            return;
+        };
+        egui::Window::new("Large graph").collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label(format!("This graph has {node_cnt} nodes and may be slow."));
+            ui.horizontal(|ui| {
+                if ui.button("Continue").clicked() {
+                    self.large_graph_prompt = None;
                 }
+                if ui.button("Load with aggressive filter").clicked() {
+                    self.filter_settings.outer_edge_cnt_filter = AGGRESSIVE_EDGE_CNT_FILTER;
+                    self.filter_settings.hide_isolated = true;
+                    self.large_graph_prompt = None;
+                }
+            });
+        });
+    }
+    fn draw_schema_warning_window(&mut self, ctx: &egui::Context) {
+        let mut report = self.load_report.write().unwrap();
+        let Some(version) = report.schema_warning else {
+            return;
+        };
+        egui::Window::new("Newer data format").collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label(format!(
+                "The data was extracted with format version {version}, but this app only supports version {SUPPORTED_SCHEMA_VERSION}. Some information may be missing, consider updating the app."
+            ));
+            if ui.button("OK").clicked() {
+                report.schema_warning = None;
             }
-
-            for &Reverse((_, oni)) in &bh {
-                let opos = self.fg.g[oni].location();
-
-                let dir = opos - pos;
-                let dis = dir.length();
-                let dir = dir.normalized();
-
-                if dis > self.force_settings.r_size {
-                    continue;
+        });
+    }
+    fn draw_restore_session_window(&mut self, ctx: &egui::Context) {
+        if self.session_to_restore.is_none() {
+            return;
+        }
+        let mut restore = None;
+        egui::Window::new("Restore previous session?").collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Restore").clicked() {
+                    restore = Some(true);
                 }
-
-                let racc = -(self.force_settings.r_force * (self.force_settings.r_size-dis));
-                let mr = self.fg.g[oni].payload().mass() / self.fg.g[ni].payload().mass();
-
-                let racc_dt = racc*dt;
-
-                self.fg.g[ni].payload_mut().vel += mr * racc_dt * dir;
-                self.fg.g[oni].payload_mut().vel += (1./mr) * racc_dt * (-dir);
+                if ui.button("Discard").clicked() {
+                    restore = Some(false);
+                }
+            });
+        });
+        match restore {
+            Some(true) => {
+                let pending = self.session_to_restore.take().unwrap();
+                self.restore_pending_session(pending);
             }
-
-            bh.push(Reverse((pos.x as i64, ni)));
+            Some(false) => self.session_to_restore = None,
+            None => {}
         }
-
-        // Apply bounding force
-        let mut center_of_mass = (Vec2::ZERO, 0.);
-
-        for &ni in &indices {
-            let mass = self.fg.g[ni].payload().mass();
-            let loc = self.fg.g[ni].location().to_vec2();
-            let tot_mass = center_of_mass.1 + mass;
-            center_of_mass.0 = (center_of_mass.1 * center_of_mass.0 + mass * loc) / tot_mass;
-            center_of_mass.1 = tot_mass;
+    }
+    fn draw_cycles_window(&mut self, ctx: &egui::Context) {
+        let Some(cycles) = &self.cycles else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Dependency cycles").open(&mut open).show(ctx, |ui| {
+            if cycles.is_empty() {
+                ui.label("No cycles found");
+                return;
+            }
+            ui.label(format!("Found {} cycles, the coloring may look odd unless cycles are broken", cycles.len()));
+            ui.checkbox(&mut self.coloring_settings.break_cycles, "Break cycles for coloring");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (i, scc) in cycles.iter().enumerate() {
+                    ui.collapsing(format!("Cycle {} ({} constants)", i + 1, scc.len()), |ui| {
+                        for &ni in scc {
+                            if let Some(node) = self.fg.node(ni) {
+                                ui.label(node.payload().name.clone());
+                            }
+                        }
+                    });
+                }
+            });
+        });
+        if !open {
+            self.clear_cycles();
+        }
+    }
+    fn update_highlight(&mut self) {
+        let node_indices = self.fg.g.node_indices().collect::<Vec<_>>();
+        for &ni in &node_indices {
+            self.fg.g[ni].payload_mut().dimmed = false;
         }
 
-        let center_of_mass = center_of_mass.0;
-        for &ni in &indices {
-            let dir =  center_of_mass - self.fg.g[ni].location().to_vec2();
-            let dis = dir.length();
-            let dir = dir.normalized();
+        let direction = match self.highlight_direction {
+            HighlightDirection::None => return,
+            HighlightDirection::Dependencies => Direction::Incoming,
+            HighlightDirection::Dependents => Direction::Outgoing,
+        };
 
-            let bacc = dis*self.force_settings.b_force;
-            self.fg.g[ni].payload_mut().vel += bacc * dt * dir;
+        let mut stack = node_indices.iter().copied().filter(|&ni| self.fg.g[ni].selected()).collect::<Vec<_>>();
+        if stack.is_empty() {
+            return;
         }
 
-        for &ni in &indices {
-            let mut cvel = self.fg.g[ni].payload().vel;
-            cvel = cvel * (1. - (self.force_settings.stiffness));
-            const SPEED_LIMIT: f32 = 10000.;
-            cvel = if cvel.length() > SPEED_LIMIT {cvel.normalized()*SPEED_LIMIT} else {cvel};
-            let pos = self.fg.g[ni].location();
-            self.fg.node_mut(ni).unwrap().payload_mut().vel = cvel;
-            self.fg.node_mut(ni).unwrap().set_location(pos + cvel * dt);
+        let mut highlighted = stack.iter().copied().collect::<HashSet<_>>();
+        while let Some(cur) = stack.pop() {
+            for oni in self.fg.g.neighbors_directed(cur, direction) {
+                if highlighted.insert(oni) {
+                    stack.push(oni);
+                }
+            }
         }
 
+        for &ni in &node_indices {
+            self.fg.g[ni].payload_mut().dimmed = !highlighted.contains(&ni);
+        }
+    }
+    /// Smoothly moves the view to the canvas position, all recentering should go through this
+    fn animate_camera_to(&mut self, center: Pos2, zoom: Option<f32>) {
+        self.camera_tween = Some(CameraTween::new(now(), center, zoom));
+    }
+    fn update_camera_tween(&mut self, ui: &mut egui::Ui) {
+        // any navigation by the user takes over the camera
+        let user_navigates = ui.ctx().input(|i| i.scroll_delta != Vec2::ZERO || i.zoom_delta() != 1.)
+            || (ui.ui_contains_pointer() && ui.ctx().input(|i| i.pointer.primary_down()));
+        if user_navigates {
+            self.camera_tween = None;
+        }
 
+        let Some(tween) = &mut self.camera_tween else {
+            return;
+        };
+        let rect = ui.max_rect();
+        if tween.apply(ui, rect, now()) {
+            self.camera_tween = None;
+        } else {
+            ui.ctx().request_repaint();
+        }
     }
-    fn draw_ui(&mut self, ctx: &eframe::egui::Context) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let interaction_settings = &SettingsInteraction::new()
-                .with_dragging_enabled(true)
-                .with_node_clicking_enabled(true)
-                .with_edge_clicking_enabled(true)
-                .with_edge_selection_enabled(true)
-                .with_node_selection_enabled(true)
-                .with_node_selection_multi_enabled(true);
+    fn update_undo(&mut self, ctx: &egui::Context) {
+        if !ctx.wants_keyboard_input() {
+            let undo = ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::Z));
+            let redo = ctx.input_mut(|i| {
+                i.consume_key(Modifiers::COMMAND, Key::Y) || i.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z)
+            });
+            if undo || redo {
+                self.commit_undo();
+            }
+            let restored = if undo {
+                self.undo_stack.undo()
+            } else if redo {
+                self.undo_stack.redo()
+            } else {
+                None
+            };
+            if let Some((settings, nodes)) = restored {
+                self.restore_undo_state(settings, nodes);
+                return;
+            }
+        }
 
-            let style_settings = &SettingsStyle::new().with_labels_always(true);
-            let navigations_settings = &SettingsNavigation::new()
-                .with_zoom_and_pan_enabled(true)
-                .with_fit_to_screen_enabled(*self.fit_to_screen.read().unwrap());
-            *self.fit_to_screen.write().unwrap() = false;
+        // only actions finished this frame are committed, so a slider drag becomes a single entry
+        // and typing into a text field is committed once the field loses focus
+        let typing = ctx.wants_keyboard_input();
+        let action_finished = ctx.input(|i| {
+            !i.pointer.any_down()
+                && (i.pointer.any_released()
+                    || (!typing && i.events.iter().any(|e| matches!(e, egui::Event::Key { .. } | egui::Event::Text(_)))))
+        });
+        if action_finished {
+            self.commit_undo();
+            self.session_changed = true;
+        }
+    }
+    fn commit_undo(&mut self) {
+        let g = self.g.read().unwrap();
+        let mut nodes = g.g.node_indices().map(|ni| (ni, NodeState::of(g.g[ni].payload()))).collect::<HashMap<_, _>>();
+        drop(g);
+        // edits of this frame are only in the filtered graph until the next rebuild
+        for ni in self.fg.g.node_indices() {
+            if self.is_visible(ni) {
+                nodes.insert(ni, NodeState::of(self.fg.g[ni].payload()));
+            }
+        }
+        self.undo_stack.commit(self.undo_settings(), nodes);
+    }
+    fn undo_settings(&self) -> UndoSettings {
+        UndoSettings {
+            filter_settings: self.filter_settings.clone(),
+            coloring_settings: self.coloring_settings.clone(),
+        }
+    }
+    fn restore_undo_state(&mut self, settings: UndoSettings, nodes: Vec<(NodeIndex<u32>, NodeState)>) {
+        self.filter_settings = settings.filter_settings;
+        self.coloring_settings = settings.coloring_settings;
+        let mut g = self.g.write().unwrap();
+        for (ni, state) in nodes {
+            if let Some(node) = g.g.node_weight_mut(ni) {
+                state.apply(node.payload_mut());
+            }
+            if self.is_visible(ni) {
+                state.apply(self.fg.g[ni].payload_mut());
+            }
+        }
+        self.coloring_cache = None;
+    }
+    fn update_history(&mut self) {
+        let selected = self.fg.g.node_indices().filter(|&ni| self.fg.g[ni].selected()).collect::<Vec<_>>();
+        let &[ni] = selected.as_slice() else {
+            return;
+        };
+        if self.fg.g[ni].payload().group.is_some() || self.history.get(self.history_pos) == Some(&ni) {
+            return;
+        }
 
-            ui.add(
+        // selecting a new node drops the forward history
+        self.history.truncate(self.history_pos + 1);
+        self.history.push_back(ni);
+        if self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history_pos = self.history.len() - 1;
+    }
+    /// Whether the node of the source graph is shown in the filtered graph
+    ///
+    /// Namespace groups only exist in the filtered graph and can reuse the index of a hidden node.
+    fn is_visible(&self, ni: NodeIndex<u32>) -> bool {
+        self.fg.g.node_weight(ni).is_some_and(|node| node.payload().group.is_none())
+    }
+    /// Selects and centers on the node at position `pos` of the history
+    fn go_to_history(&mut self, pos: usize) {
+        let ni = self.history[pos];
+        if !self.is_visible(ni) {
+            return;
+        }
+        self.history_pos = pos;
+        self.select_and_center(ni);
+    }
+    /// Makes the node the only selected one and moves the view to it
+    fn select_and_center(&mut self, ni: NodeIndex<u32>) {
+        for oni in self.fg.g.node_indices().collect::<Vec<_>>() {
+            self.fg.g[oni].set_selected(oni == ni);
+        }
+        let location = self.fg.g[ni].location();
+        self.animate_camera_to(location, None);
+    }
+    fn run_palette_item(&mut self, ctx: &egui::Context, item: PaletteItem) {
+        match item {
+            PaletteItem::Command(Command::FitToScreen) => *self.fit_to_screen.write().unwrap() = true,
+            PaletteItem::Command(Command::SaveVisualization) => self.save_viz_dialog(),
+            PaletteItem::Command(Command::ToggleDarkMode) => toggle_dark_mode(ctx),
+            PaletteItem::Command(Command::RandomizeColors) => {
+                self.coloring_settings.color_seed = random::<u32>() as u64;
+                self.randomize_colors();
+            }
+            PaletteItem::Node(ni) => {
+                if self.fg.g.contains_node(ni) {
+                    self.select_and_center(ni);
+                }
+            }
+        }
+    }
+    /// Recolors the nodes from the color seed, the same seed always gives the same colors
+    fn randomize_colors(&mut self) {
+        let mut g = self.g.write().unwrap();
+        let node_indices = g.g.node_indices().filter(|&ni| self.coloring_settings.randomize_hidden || self.is_visible(ni)).collect::<Vec<_>>();
+        // colors are drawn even for locked nodes, so locking doesn't shift the colors of the others
+        let colors = seeded_colors(self.coloring_settings.color_seed, node_indices.len());
+        for (ni, color) in node_indices.into_iter().zip(colors) {
+            let visible = self.is_visible(ni);
+            if g.g[ni].payload().color_locked {
+                continue;
+            }
+            g.g[ni].payload_mut().color = color;
+            if visible {
+                self.fg.g[ni].payload_mut().color = color;
+            }
+        }
+        self.coloring_cache = None;
+    }
+    fn save_viz_dialog(&self) {
+        let data_to_store = serde_json::to_string(&self.save_viz()).unwrap();
+        spawn_local(async move {
+            let Some(file_handle) = AsyncFileDialog::new().add_filter("Lean Graph", &["leangraph"]).set_file_name("untitled.leangraph").save_file().await else {
+                return;
+            };
+            file_handle.write(data_to_store.as_bytes()).await.unwrap();
+        })
+    }
+    /// Explains why nothing is shown instead of leaving the canvas blank
+    fn draw_empty_state(&mut self, ui: &egui::Ui) {
+        if self.fg.g.node_count() > 0 || self.load_progress.read().unwrap().is_some() {
+            return;
+        }
+        let source_empty = self.g.read().unwrap().g.node_count() == 0;
+        egui::Area::new("empty_state").fixed_pos(ui.max_rect().center()).pivot(egui::Align2::CENTER_CENTER).show(ui.ctx(), |ui| {
+            ui.vertical_centered(|ui| {
+                if source_empty {
+                    ui.label("The opened data contains no constants, open another file");
+                } else {
+                    ui.label("All constants are hidden by the filters");
+                    if ui.button("Reset filters").clicked() {
+                        self.filter_settings = FilterSettings::default();
+                    }
+                }
+            });
+        });
+    }
+    fn draw_breadcrumbs(&mut self, ui: &egui::Ui) {
+        if self.history.is_empty() {
+            return;
+        }
+        let g = self.g.read().unwrap();
+        let mut go_to = None;
+        egui::Area::new("breadcrumbs").fixed_pos(ui.max_rect().left_top() + Vec2::splat(8.)).show(ui.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.history_pos > 0, egui::Button::new("◀")).clicked() {
+                    go_to = Some(self.history_pos - 1);
+                }
+                if ui.add_enabled(self.history_pos + 1 < self.history.len(), egui::Button::new("▶")).clicked() {
+                    go_to = Some(self.history_pos + 1);
+                }
+                for (i, &ni) in self.history.iter().enumerate() {
+                    let Some(node) = g.g.node_weight(ni) else {
+                        continue;
+                    };
+                    if i > 0 {
+                        ui.label("›");
+                    }
+                    let enabled = self.is_visible(ni);
+                    if ui.add_enabled(enabled, egui::SelectableLabel::new(i == self.history_pos, node.payload().name.clone())).clicked() {
+                        go_to = Some(i);
+                    }
+                }
+            });
+        });
+        drop(g);
+        if let Some(pos) = go_to {
+            self.go_to_history(pos);
+        }
+    }
+    /// Frames the whole graph including the extent of the nodes, unlike fitting only their centers
+    fn fit_with_padding(&mut self, ui: &mut egui::Ui) {
+        let Some(bounds) = graph_bounds(&self.fg) else {
+            return;
+        };
+        self.camera_tween = None;
+        let rect = ui.max_rect();
+        fit_to_rect(ui, rect, bounds, FIT_PADDING);
+    }
+    fn update_fit_until(&mut self, ct: Duration) {
+        let Some(fit_until) = self.fit_until else {
+            return;
+        };
+
+        if ct >= fit_until {
+            self.fit_until = None;
+        } else {
+            self.fit_whole_graph = true;
+        }
+    }
+    fn update_dragged(&mut self, ctx: &egui::Context) {
+        let dragged_nodes = self.fg.g.node_indices().filter(|&ni| self.fg.g[ni].dragged()).collect::<Vec<_>>();
+
+        // dropping a node with shift held pins it permanently
+        if ctx.input(|i| i.modifiers.shift) {
+            for &ni in &self.dragged_nodes {
+                if !dragged_nodes.contains(&ni) && self.fg.g.contains_node(ni) {
+                    self.fg.g[ni].payload_mut().pinned = true;
+                }
+            }
+        }
+
+        self.move_selection_along(&dragged_nodes);
+        self.dragged_nodes = dragged_nodes;
+    }
+    /// Moves the other selected nodes by as much as the dragged node moved since the last frame
+    fn move_selection_along(&mut self, dragged_nodes: &[NodeIndex<u32>]) {
+        let Some(&ni) = dragged_nodes.first() else {
+            self.drag_anchor = None;
+            return;
+        };
+        let location = self.fg.g[ni].location();
+        let delta = match self.drag_anchor {
+            Some((ani, prev)) if ani == ni => location - prev,
+            _ => Vec2::ZERO,
+        };
+        self.drag_anchor = Some((ni, location));
+        if delta == Vec2::ZERO || !self.fg.g[ni].selected() {
+            return;
+        }
+
+        for oni in self.fg.g.node_indices().collect::<Vec<_>>() {
+            if oni == ni || !self.fg.g[oni].selected() {
+                continue;
+            }
+            let node = &mut self.fg.g[oni];
+            let new_location = node.location() + delta;
+            node.set_location(new_location);
+            let payload = node.payload_mut();
+            payload.vel = Vec2::ZERO;
+            if payload.fixed_location.is_some() {
+                payload.fixed_location = Some(new_location);
+            }
+        }
+    }
+    /// Nodes that are held in place, either pinned, fixed or currently being dragged
+    fn frozen_nodes(&self) -> HashSet<NodeIndex<u32>> {
+        let dragging = !self.dragged_nodes.is_empty();
+        self.fg.g.node_indices().filter(|&ni| {
+            let node = &self.fg.g[ni];
+            node.payload().pinned || node.payload().fixed_location.is_some() || node.dragged() || (dragging && node.selected())
+        }).collect()
+    }
+    fn simulate_force_graph(&mut self, dt: f32) {
+        let frozen = self.frozen_nodes();
+        force::simulate_force_graph(&mut self.fg, &self.force_settings, &frozen, dt);
+    }
+    fn draw_ui(&mut self, ctx: &eframe::egui::Context) {
+        self.display_settings.large_graph = self.fg.g.edge_count() > self.display_settings.edge_flow_max_edges;
+        self.display_settings.store_into_ctx(ctx);
+        if self.display_settings.edge_flow {
+            ctx.request_repaint();
+        }
+        // arrow keys belong to the palette while it's open
+        let table_keyboard = !self.command_palette.is_open();
+        if let Some(ni) = self.node_table.show(ctx, &self.fg, table_keyboard) {
+            self.select_and_center(ni);
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let interaction_settings = &SettingsInteraction::new()
+                .with_dragging_enabled(true)
+                .with_node_clicking_enabled(true)
+                .with_edge_clicking_enabled(true)
+                .with_edge_selection_enabled(true)
+                .with_node_selection_enabled(true)
+                .with_node_selection_multi_enabled(true);
+
+            let style_settings = &SettingsStyle::new().with_labels_always(true);
+            let navigations_settings = &SettingsNavigation::new()
+                .with_zoom_and_pan_enabled(true)
+                .with_fit_to_screen_enabled(*self.fit_to_screen.read().unwrap());
+            *self.fit_to_screen.write().unwrap() = false;
+
+            self.update_camera_tween(ui);
+            if std::mem::take(&mut self.fit_whole_graph) {
+                self.fit_with_padding(ui);
+            }
+
+            let response = ui.add(
                 &mut GraphView::new(&mut self.fg)
                     .with_styles(style_settings)
                     .with_navigations(navigations_settings)
                     .with_interactions(interaction_settings),
             );
 
+            // redraw emphasized nodes last, so they aren't hidden under their neighbors
+            let meta = Metadata::get(ui);
+            let hovered = self.hovered_node(ui);
+            self.display_settings.hovered = hovered;
+            for ni in self.fg.g.node_indices() {
+                if self.fg.g[ni].selected() || Some(ni) == hovered {
+                    ui.painter().extend(self.fg.g[ni].display().draw(ui.ctx(), &meta));
+                }
+            }
+
+            self.draw_breadcrumbs(ui);
+            self.draw_empty_state(ui);
+
+            if let Some(progress) = *self.load_progress.read().unwrap() {
+                egui::Window::new("Loading").collapsible(false).resizable(false).show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.add(egui::ProgressBar::new(progress).show_percentage());
+                    });
+                });
+                ctx.request_repaint();
+            }
+
+            let dragging = ctx.input(|i| i.pointer.any_down()) || !self.dragged_nodes.is_empty();
+            if response.hovered() && !dragging {
+                self.draw_hover_tooltip(ui);
+            }
+
             let g = self.g.read().unwrap();
             let node_indices = g.g.node_indices().clone().collect::<Vec<_>>();
+            let mut node_edits = vec![];
+            let mut center_on = None;
             for ni in node_indices {
                 if g.g[ni].selected() {
                     let data = g.g[ni].payload();
                     egui::Window::new(data.name.clone()).show(ctx, |ui| {
                         ui.label(data.const_type.clone());
+                        ui.horizontal(|ui| {
+                            let mut pinned = data.pinned;
+                            if ui.checkbox(&mut pinned, "Pinned").changed() {
+                                node_edits.push((ni, NodeEdit::Pinned(pinned)));
+                            }
+                            let mut fixed = data.fixed_location.is_some();
+                            if ui.checkbox(&mut fixed, "Fix position").on_hover_text("Keep the node at its location, even across filter changes and reloads").changed() {
+                                node_edits.push((ni, NodeEdit::Fixed(fixed)));
+                            }
+                            if ui.button("Center").clicked() {
+                                center_on = Some(g.g[ni].location());
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let mut color = data.color;
+                            if egui::color_picker::color_edit_button_rgb(ui, &mut color).changed() {
+                                node_edits.push((ni, NodeEdit::Color(color)));
+                            }
+                            let mut color_locked = data.color_locked;
+                            if ui.checkbox(&mut color_locked, "Keep color").on_hover_text("Exclude from color randomization").changed() {
+                                node_edits.push((ni, NodeEdit::ColorLocked(color_locked)));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Highlight:");
+                            ui.radio_value(&mut self.highlight_direction, HighlightDirection::None, "None");
+                            ui.radio_value(&mut self.highlight_direction, HighlightDirection::Dependencies, "Dependencies");
+                            ui.radio_value(&mut self.highlight_direction, HighlightDirection::Dependents, "Dependents");
+                        });
+                        if let Some(location) = data.source_location() {
+                            ui.separator();
+                            ui.horizontal(|ui| {
+                                ui.label(location.clone());
+                                if ui.button("Copy location").clicked() {
+                                    ui.output_mut(|o| o.copied_text = location);
+                                }
+                            });
+                            if let Some(link) = data.source_link() {
+                                ui.add(Hyperlink::from_label_and_url("Open definition", link));
+                            }
+                        }
                     });
                 }
             }
+            drop(g);
+            if let Some(pos) = center_on {
+                self.animate_camera_to(pos, None);
+            }
+            for (ni, edit) in node_edits {
+                if !self.is_visible(ni) {
+                    continue;
+                }
+                let node = &mut self.fg.g[ni];
+                let location = node.location();
+                let payload = node.payload_mut();
+                match edit {
+                    NodeEdit::Pinned(pinned) => payload.pinned = pinned,
+                    NodeEdit::Fixed(fixed) => payload.fixed_location = fixed.then_some(location),
+                    NodeEdit::Color(color) => {
+                        // a manually picked color should stick
+                        payload.color = color;
+                        payload.color_locked = true;
+                        self.coloring_cache = None;
+                    }
+                    NodeEdit::ColorLocked(color_locked) => {
+                        // locked nodes keep their own color in the propagation
+                        payload.color_locked = color_locked;
+                        self.coloring_cache = None;
+                    }
+                }
+            }
         });
         egui::SidePanel::new(egui::panel::Side::Right, "Settings").show(ctx, |ui| {
             ui.collapsing("File", |ui| {
@@ -406,17 +1655,29 @@ impl MApp {
                             // download file from server and set it as current graph
                             let gc = self.g.clone();
                             let guc = self.g_updated.clone();
+                            let lpc = self.load_progress.clone();
+                            let lrc = self.load_report.clone();
+                            let create_placeholders = self.create_placeholders;
 
                             spawn_local(async move {
-                                let ng_raw = read_graph_url(&format!(
-                                    "{SERVER_ADDR}/static/{server_file_name}"
-                                ))
-                                .await
-                                .unwrap();
-                                let ng = load_graph(ng_raw);
-
-                                *gc.write().unwrap() = ng.clone();
-                                *guc.write().unwrap() = true;
+                                *lpc.write().unwrap() = Some(0.);
+                                let ng_raw = match read_graph_url(&format!("{SERVER_ADDR}/static/{server_file_name}")).await {
+                                    Ok(ng_raw) => ng_raw,
+                                    Err(err) => {
+                                        log::error!("failed to load {server_file_name}: {err}");
+                                        *lpc.write().unwrap() = None;
+                                        return;
+                                    }
+                                };
+                                let Some((ng, report)) = load_graph_async(ng_raw, create_placeholders, lpc.clone()).await else {
+                                    return;
+                                };
+
+                                publish_graph(&gc, &guc, |g| {
+                                    *g = ng;
+                                    *lrc.write().unwrap() = report;
+                                });
+                                *lpc.write().unwrap() = None;
                             })
                         }
                     }
@@ -424,17 +1685,86 @@ impl MApp {
                 if ui.button("Open extracted data").clicked() {
                     let gc = self.g.clone();
                     let guc = self.g_updated.clone();
-                    let ftsc = self.fit_to_screen.clone();
+                    let lpc = self.load_progress.clone();
+                    let lrc = self.load_report.clone();
+                    let create_placeholders = self.create_placeholders;
                     spawn_local(async move {
                         let Some(ng_raw) = read_graph_file_dialog().await else {
                             return;
                         };
-                        let ng = load_graph(ng_raw);
-                        *gc.write().unwrap() = ng.clone();
-                        *guc.write().unwrap() = true;
-                        *ftsc.write().unwrap() = true;
+                        *lpc.write().unwrap() = Some(0.);
+                        let Some((ng, report)) = load_graph_async(ng_raw, create_placeholders, lpc.clone()).await else {
+                            return;
+                        };
+                        publish_graph(&gc, &guc, |g| {
+                            *g = ng;
+                            *lrc.write().unwrap() = report;
+                        });
+                        *lpc.write().unwrap() = None;
                     });
                 }
+                if ui.button("Add extracted data").on_hover_text("Merge another extraction into the current graph").clicked() {
+                    let gc = self.g.clone();
+                    let guc = self.g_updated.clone();
+                    let lpc = self.load_progress.clone();
+                    let lrc = self.load_report.clone();
+                    let create_placeholders = self.create_placeholders;
+                    spawn_local(async move {
+                        let Some(ng_raw) = read_graph_file_dialog().await else {
+                            return;
+                        };
+                        *lpc.write().unwrap() = Some(0.);
+                        let Some((ng, report)) = load_graph_async(ng_raw, create_placeholders, lpc.clone()).await else {
+                            return;
+                        };
+                        publish_graph(&gc, &guc, |g| {
+                            merge_graph(g, ng);
+                            let mut lr = lrc.write().unwrap();
+                            lr.unresolved.extend(report.unresolved);
+                            lr.schema_warning = lr.schema_warning.max(report.schema_warning);
+                        });
+                        *lpc.write().unwrap() = None;
+                    });
+                }
+                if ui.button("Compare with…").on_hover_text("Show which constants and references another extraction adds or removes").clicked() {
+                    let gc = self.g.clone();
+                    let guc = self.g_updated.clone();
+                    let lpc = self.load_progress.clone();
+                    let gdc = self.graph_diff.clone();
+                    let create_placeholders = self.create_placeholders;
+                    spawn_local(async move {
+                        let Some(ng_raw) = read_graph_file_dialog().await else {
+                            return;
+                        };
+                        *lpc.write().unwrap() = Some(0.);
+                        let Some((ng, _)) = load_graph_async(ng_raw, create_placeholders, lpc.clone()).await else {
+                            return;
+                        };
+                        publish_graph(&gc, &guc, |g| *gdc.write().unwrap() = Some(diff_graph(g, ng)));
+                        *lpc.write().unwrap() = None;
+                    });
+                }
+                let graph_diff = self.graph_diff.read().unwrap().clone();
+                if let Some(graph_diff) = graph_diff {
+                    ui.label(format!(
+                        "Constants +{} −{}, references +{} −{}",
+                        graph_diff.added_nodes, graph_diff.removed_nodes, graph_diff.added_edges, graph_diff.removed_edges
+                    ));
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.display_settings.show_diff, "Show comparison");
+                        if ui.button("Clear comparison").clicked() {
+                            self.clear_comparison();
+                        }
+                    });
+                }
+                ui.checkbox(&mut self.create_placeholders, "Create nodes for unresolved references")
+                    .on_hover_text("Applies to graphs opened afterwards");
+                ui.label("Warn about graphs with more nodes than");
+                ui.add(Slider::new(&mut self.large_graph_threshold, 1000..=100000).logarithmic(true));
+                let unresolved_cnt = self.load_report.read().unwrap().unresolved.len();
+                if ui.button(format!("Load report ({unresolved_cnt} unresolved)")).clicked() {
+                    self.show_load_report = !self.show_load_report;
+                }
                 if ui.button("Open stored visualization").clicked() {
                     let data_to_load = self.data_to_load.clone();
                     spawn_local(async move {
@@ -451,12 +1781,18 @@ impl MApp {
                     })
                 }
                 if ui.button("Save visualization").clicked() {
-                    let data_to_store = serde_json::to_string(&self.save_viz()).unwrap();
+                    self.save_viz_dialog();
+                }
+                if self.autosave_failed {
+                    ui.colored_label(ui.visuals().warn_fg_color, "Autosave failed, save the visualization to keep it");
+                }
+                if ui.button("Export layout JSON").on_hover_text("Node positions, colors and edges for rendering in other tools").clicked() {
+                    let layout = export_layout(&self.fg);
                     spawn_local(async move {
-                        let Some(file_handle) = AsyncFileDialog::new().add_filter("Lean Graph", &["leangraph"]).set_file_name("untitled.leangraph").save_file().await else {
+                        let Some(file_handle) = AsyncFileDialog::new().add_filter("Json", &["json"]).set_file_name("layout.json").save_file().await else {
                             return;
                         };
-                        file_handle.write(data_to_store.as_bytes()).await.unwrap();
+                        file_handle.write(layout.as_bytes()).await.unwrap();
                     })
                 }
                 if ui.button("Download dependency extractor").clicked() {
@@ -480,11 +1816,31 @@ impl MApp {
                     &mut self.force_settings.e_force,
                     (0.0)..=(0.002),
                 ));
+                ui.horizontal(|ui| {
+                    ui.label("Attraction growth");
+                    egui::ComboBox::from_id_source("attraction_mode")
+                        .selected_text(format!("{:?}", self.force_settings.attraction_mode))
+                        .show_ui(ui, |ui| {
+                            for mode in AttractionMode::ALL {
+                                ui.selectable_value(&mut self.force_settings.attraction_mode, mode, format!("{mode:?}"));
+                            }
+                        });
+                }).response.on_hover_text("How strongly long edges pull compared to short ones");
                 ui.label("Repulsion force");
                 ui.add(Slider::new(
                     &mut self.force_settings.r_force,
                     (10.)..=(1000.),
                 ));
+                ui.horizontal(|ui| {
+                    ui.label("Repulsion falloff");
+                    egui::ComboBox::from_id_source("repulsion_mode")
+                        .selected_text(format!("{:?}", self.force_settings.repulsion_mode))
+                        .show_ui(ui, |ui| {
+                            for mode in RepulsionMode::ALL {
+                                ui.selectable_value(&mut self.force_settings.repulsion_mode, mode, format!("{mode:?}"));
+                            }
+                        });
+                }).response.on_hover_text("Smooth falloffs avoid jitter of nodes at the repulsion size");
                 ui.label("Republsion size");
                 ui.add(Slider::new(
                     &mut self.force_settings.r_size,
@@ -497,56 +1853,210 @@ impl MApp {
                 ));
                 ui.label("Stifness");
                 ui.add(Slider::new(&mut self.force_settings.stiffness, (0.)..=1.));
+                ui.label("Mass exponent");
+                ui.add(Slider::new(&mut self.force_settings.mass_exponent, (0.)..=2.))
+                    .on_hover_text("0 makes all nodes equally heavy, 1 makes mass proportional to size");
+                ui.checkbox(&mut self.force_settings.symmetric_attraction, "Symmetric attraction")
+                    .on_hover_text("Edges pull both of their nodes together, which tends to form tighter clusters. Arrows stay directed.");
+                ui.horizontal(|ui| {
+                    let mut capped = self.force_settings.hub_attraction_cap.is_some();
+                    if ui.checkbox(&mut capped, "Cap hub attraction").on_hover_text("Limits how much harder small nodes are pulled towards heavy hubs, keeping local clusters readable").changed() {
+                        self.force_settings.hub_attraction_cap = capped.then_some(10.);
+                    }
+                    if let Some(cap) = &mut self.force_settings.hub_attraction_cap {
+                        ui.add(Slider::new(cap, (1.)..=(1000.)).logarithmic(true));
+                    }
+                });
+                ui.label("Speed limit");
+                ui.add(Slider::new(&mut self.force_settings.speed_limit, force::MIN_SPEED_LIMIT..=force::MAX_SPEED_LIMIT).logarithmic(true));
+                ui.label("Time step");
+                ui.add(Slider::new(&mut self.force_settings.fixed_dt, (1. / 480.)..=(1. / 30.)));
+                ui.label("Max steps per frame");
+                ui.add(Slider::new(&mut self.force_settings.max_substeps, 1..=32));
+                ui.horizontal(|ui| {
+                    if ui.button("Explode").on_hover_text("Push all nodes away from the center to untangle a collapsed layout").clicked() {
+                        let frozen = self.frozen_nodes();
+                        force::explode(&mut self.fg, &self.force_settings, &frozen);
+                    }
+                    ui.add(Slider::new(&mut self.force_settings.explode_strength, (0.)..=(300.)));
+                });
             });
             ui.collapsing("Coloring", |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Mode");
+                    egui::ComboBox::from_id_source("coloring_mode")
+                        .selected_text(format!("{:?}", self.coloring_settings.mode))
+                        .show_ui(ui, |ui| {
+                            for mode in ColoringMode::ALL {
+                                ui.selectable_value(&mut self.coloring_settings.mode, mode, format!("{mode:?}"));
+                            }
+                        });
+                });
+                if let (ColoringMode::Heatmap, Some((min, max))) = (self.coloring_settings.mode, self.heat_range) {
+                    ui.label("Referenced by");
+                    ui.horizontal(|ui| {
+                        ui.label(min.to_string());
+                        let (rect, _) = ui.allocate_exact_size(egui::vec2(120., 12.), egui::Sense::hover());
+                        const COLORBAR_STEPS: usize = 24;
+                        let step = rect.width() / COLORBAR_STEPS as f32;
+                        for i in 0..COLORBAR_STEPS {
+                            let segment = egui::Rect::from_min_size(rect.min + Vec2::X * step * i as f32, egui::vec2(step + 0.5, rect.height()));
+                            ui.painter().rect_filled(segment, 0., viridis(i as f32 / (COLORBAR_STEPS - 1) as f32));
+                        }
+                        ui.label(max.to_string());
+                    });
+                }
                 ui.label("Node coloring loss");
                 ui.add(Slider::new(
                     &mut self.coloring_settings.color_loss,
                     (0.0)..=1.0,
                 ));
-                if ui.button("Randomize colors").clicked() {
-                    for ni in self.fg.g.node_indices().collect::<Vec<_>>() {
-                        self.fg.g[ni].payload_mut().color = random_node_color();
+                ui.horizontal(|ui| {
+                    if ui.button("Randomize colors").clicked() {
+                        self.coloring_settings.color_seed = random::<u32>() as u64;
+                        self.randomize_colors();
+                    }
+                    ui.label("Seed");
+                    if ui.add(egui::DragValue::new(&mut self.coloring_settings.color_seed)).changed() {
+                        self.randomize_colors();
                     }
+                });
+                ui.checkbox(&mut self.coloring_settings.randomize_hidden, "Include nodes hidden by filters");
+            });
+
+            ui.collapsing("Analysis", |ui| {
+                if ui.button("Detect cycles").clicked() {
+                    self.detect_cycles();
                 }
             });
 
             ui.collapsing("Filter", |ui| {
-                ui.checkbox(
-                    self.filter_settings.node_type_filter.get_mut(&ConstCategory::Axiom).unwrap(),
-                    "Axioms",
-                );
-                ui.checkbox(
-                    self.filter_settings.node_type_filter.get_mut(&ConstCategory::Theorem).unwrap(),
-                    "Theorems",
-                );
-                ui.checkbox(
-                    self.filter_settings.node_type_filter
-                        .get_mut(&ConstCategory::Definition)
-                        .unwrap(),
-                    "Definitions",
-                );
-                ui.checkbox(
-                    self.filter_settings.node_type_filter.get_mut(&ConstCategory::Other).unwrap(),
-                    "Other",
-                );
+                ui.horizontal(|ui| {
+                    if ui.button("All").clicked() {
+                        self.filter_settings.node_type_filter.values_mut().for_each(|shown| *shown = true);
+                    }
+                    if ui.button("None").clicked() {
+                        self.filter_settings.node_type_filter.values_mut().for_each(|shown| *shown = false);
+                    }
+                });
+                for (category, label) in [
+                    (ConstCategory::Axiom, "Axioms"),
+                    (ConstCategory::Theorem, "Theorems"),
+                    (ConstCategory::Definition, "Definitions"),
+                    (ConstCategory::Other, "Other"),
+                ] {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(
+                            self.filter_settings.node_type_filter.get_mut(&category).unwrap(),
+                            label,
+                        );
+                        if ui.small_button("solo").on_hover_text("Show only this category").clicked() {
+                            for (other, shown) in self.filter_settings.node_type_filter.iter_mut() {
+                                *shown = *other == category;
+                            }
+                        }
+                    });
+                }
                 ui.label("Max node out-degree");
                 ui.add(Slider::new(&mut self.filter_settings.outer_edge_cnt_filter, 1..=1000));
+                ui.checkbox(&mut self.filter_settings.hide_isolated, "Hide isolated constants");
+                ui.label("Group by namespace depth");
+                ui.add(Slider::new(&mut self.filter_settings.group_depth, 0..=5))
+                    .on_hover_text("Select a group to expand it");
+                if ui.add_enabled(!self.filter_settings.expanded_groups.is_empty(), egui::Button::new("Collapse groups")).clicked() {
+                    self.filter_settings.expanded_groups.clear();
+                }
+                ui.label("Type contains");
+                let mut type_contains = self.filter_settings.type_contains.clone().unwrap_or_default();
+                if ui.text_edit_singleline(&mut type_contains).changed() {
+                    self.filter_settings.type_contains = (!type_contains.is_empty()).then_some(type_contains);
+                }
+                ui.separator();
+                ui.label("Focus hops");
+                ui.add(Slider::new(&mut self.filter_settings.focus_hops, 0..=10));
+                ui.horizontal(|ui| {
+                    if ui.button("Focus (k hops)").on_hover_text("Show only the neighborhood of the selected nodes").clicked() {
+                        let selected = self.fg.g.node_indices()
+                            .filter(|&ni| self.fg.g[ni].selected() && self.fg.g[ni].payload().group.is_none())
+                            .collect::<Vec<_>>();
+                        if !selected.is_empty() {
+                            self.filter_settings.focus = Some(selected);
+                        }
+                    }
+                    if ui.add_enabled(self.filter_settings.focus.is_some(), egui::Button::new("Clear focus")).clicked() {
+                        self.filter_settings.focus = None;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Isolate selection").on_hover_text("Show only the connected components of the selected nodes").clicked() {
+                        let selected = self.fg.g.node_indices()
+                            .filter(|&ni| self.fg.g[ni].selected() && self.fg.g[ni].payload().group.is_none())
+                            .collect::<Vec<_>>();
+                        if !selected.is_empty() {
+                            self.filter_settings.isolate = Some(selected);
+                        }
+                    }
+                    if ui.add_enabled(self.filter_settings.isolate.is_some(), egui::Button::new("Show all")).clicked() {
+                        self.filter_settings.isolate = None;
+                    }
+                });
             });
 
             ui.collapsing("Style", |ui| {
                 let dark_mode = ui.ctx().style().visuals.dark_mode;
                 if ui.button(format!("Toggle {} mode", if dark_mode {"light"} else {"dark"})).clicked() {
-                    if dark_mode {
-                        ui.ctx().set_visuals(Visuals::light());
+                    toggle_dark_mode(ui.ctx());
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Fit to screen").clicked() {
+                        *self.fit_to_screen.write().unwrap() = true;
                     }
-                    else {
-                        ui.ctx().set_visuals(Visuals::dark());
+                    if ui.button("Fit whole graph").on_hover_text("Fit including the size of the nodes, so big nodes aren't cut at the border").clicked() {
+                        self.fit_whole_graph = true;
                     }
+                });
+                ui.checkbox(&mut self.node_table.open, "Show node table")
+                    .on_hover_text("Sortable list of the shown constants, arrow keys move the selection");
+                ui.label("Node size scale");
+                ui.add(Slider::new(&mut self.display_settings.node_size_scale, (0.1)..=(5.)));
+                ui.label("Min node radius");
+                ui.add(Slider::new(&mut self.display_settings.node_size_min, (1.)..=(50.)));
+                ui.label("Max node radius");
+                ui.add(Slider::new(&mut self.display_settings.node_size_max, (10.)..=(1000.)));
+                ui.label("Min label radius");
+                ui.add(Slider::new(&mut self.display_settings.label_min_radius, (0.)..=(50.)));
+                ui.label("Always label largest");
+                ui.add(Slider::new(&mut self.display_settings.label_top_n, 0..=100));
+                ui.label("Edge opacity");
+                if dark_mode {
+                    ui.add(Slider::new(&mut self.display_settings.edge_alpha_dark, 0..=255));
+                } else {
+                    ui.add(Slider::new(&mut self.display_settings.edge_alpha_light, 0..=255));
                 }
-                if ui.button("Fit to screen").clicked() {
-                    *self.fit_to_screen.write().unwrap() = true;
-                }
+                ui.label("Selected edge opacity");
+                ui.add(Slider::new(&mut self.display_settings.edge_alpha_selected, 0..=255));
+                ui.checkbox(&mut self.display_settings.highlight_roots_leaves, "Highlight roots and leaves")
+                    .on_hover_text("Roots (gold) are not used by anything, leaves (blue) depend on nothing");
+                ui.checkbox(&mut self.display_settings.highlight_duplicates, "Highlight merged duplicates")
+                    .on_hover_text("Outline constants that appeared in more than one added extraction");
+                let duplicates = self.g.read().unwrap().g.node_weights().filter(|node| node.payload().source_count > 1).count();
+                ui.label(format!("{duplicates} constants appeared in several extractions"));
+                ui.checkbox(&mut self.display_settings.edge_flow, "Animate edge direction")
+                    .on_hover_text("Dots flow from the referenced constant to the one referencing it");
+                ui.label("Node shapes");
+                egui::Grid::new("node_shapes").show(ui, |ui| {
+                    for (category, shape) in self.display_settings.node_shapes.iter_mut() {
+                        ui.label(format!("{category:?}"));
+                        egui::ComboBox::from_id_source(category)
+                            .selected_text(format!("{shape:?}"))
+                            .show_ui(ui, |ui| {
+                                for kind in NodeShapeKind::ALL {
+                                    ui.selectable_value(shape, kind, format!("{kind:?}"));
+                                }
+                            });
+                        ui.end_row();
+                    }
+                });
             });
 
 
@@ -557,20 +2067,93 @@ impl MApp {
                 ui.add(Hyperlink::from_label_and_url("GitHub", "https://github.com/patrik-cihal/lean-graph"));
             });
         });
+        self.draw_cycles_window(ctx);
+        self.draw_restore_session_window(ctx);
+        self.draw_large_graph_window(ctx);
+        self.draw_schema_warning_window(ctx);
+
+        let chosen = self.command_palette.show(ctx, self.fg.g.node_indices().map(|ni| (ni, self.fg.g[ni].payload().name.as_str())));
+        if let Some(item) = chosen {
+            self.run_palette_item(ctx, item);
+        }
+        self.draw_load_report_window(ctx);
     }
     fn update_filter_graph(&mut self) {
         let mut g = self.g.write().unwrap();
         if !*self.g_updated.read().unwrap() {
             for &ni in &self.fg.g.node_indices().collect::<Vec<_>>() {
-                let cur_node = self.fg.g[ni].clone();
+                let mut cur_node = self.fg.g[ni].clone();
+                // namespace groups only exist in the filtered graph
+                if let Some(group) = &cur_node.payload().group {
+                    if cur_node.selected() {
+                        self.filter_settings.expanded_groups.insert(group.clone());
+                    }
+                    self.group_states.insert(group.clone(), GroupState {
+                        location: cur_node.location(),
+                        vel: cur_node.payload().vel,
+                        comp_color: cur_node.payload().comp_color,
+                        heat: cur_node.payload().heat,
+                    });
+                    continue;
+                }
+                // dragging a pinned or fixed node moves its fixed location along
+                if self.dragged_nodes.contains(&ni) && (cur_node.payload().pinned || cur_node.payload().fixed_location.is_some()) {
+                    cur_node.payload_mut().fixed_location = Some(cur_node.location());
+                }
                 *g.g.node_weight_mut(ni).unwrap() = cur_node;
             }
         }
+        else {
+            self.coloring_cache = None;
+            self.fit_until = Some(now() + FIT_AFTER_LOAD);
+            self.undo_stack.reset();
+            self.group_states.clear();
+            self.history.clear();
+            if g.g.node_weights().all(|node| node.payload().diff.is_none()) {
+                *self.graph_diff.write().unwrap() = None;
+            } else if self.fg.g.node_weights().all(|node| node.payload().diff.is_none()) {
+                // a comparison just finished, show its result
+                self.display_settings.show_diff = true;
+            }
+            self.large_graph_prompt = (g.g.node_count() > self.large_graph_threshold).then_some(g.g.node_count());
+            // a restored session is already shown with its graph
+            let key = session_key(&g);
+            if key != self.session_key {
+                self.session_key = key;
+                self.session_lookup = true;
+            }
+        }
         *self.g_updated.write().unwrap() = false;
+
+        // focus mode overrides the other filters with the neighborhood of the focused nodes
+        let focused = self.filter_settings.focus.as_ref().map(|focus| {
+            let mut visited = focus.iter().copied().filter(|&ni| g.g.contains_node(ni)).collect::<HashSet<_>>();
+            let mut layer = visited.iter().copied().collect::<Vec<_>>();
+            for _ in 0..self.filter_settings.focus_hops {
+                let mut next_layer = vec![];
+                for ni in layer {
+                    for oni in g.g.neighbors_undirected(ni) {
+                        if visited.insert(oni) {
+                            next_layer.push(oni);
+                        }
+                    }
+                }
+                layer = next_layer;
+            }
+            visited
+        });
+
+        let type_contains = self.filter_settings.type_contains.as_ref().map(|needle| needle.to_lowercase());
+
         self.fg = G::new(g.g.filter_map(
             |ni, node| {
+                if let Some(focused) = &focused {
+                    return focused.contains(&ni).then(|| node.clone());
+                }
                 if self.filter_settings.node_type_filter[&node.payload().const_category]
                     && g.g.neighbors(ni).count() <= self.filter_settings.outer_edge_cnt_filter
+                    && type_contains.as_ref().map_or(true, |needle| node.payload().const_type.to_lowercase().contains(needle))
+                    && !(self.filter_settings.hide_isolated && g.g.neighbors_undirected(ni).next().is_none())
                 {
                     Some(node.clone())
                 } else {
@@ -579,6 +2162,109 @@ impl MApp {
             },
             |_, edge| Some(edge.clone()),
         ));
+        drop(g);
+
+        if let Some(isolate) = &self.filter_settings.isolate {
+            let components = weak_components(&self.fg.g);
+            let kept = isolate.iter().filter_map(|ni| components.get(ni)).collect::<HashSet<_>>();
+            for ni in self.fg.g.node_indices().collect::<Vec<_>>() {
+                if !kept.contains(&components[&ni]) {
+                    self.fg.g.remove_node(ni);
+                }
+            }
+        }
+
+        for ni in self.fg.g.node_indices().collect::<Vec<_>>() {
+            if let Some(location) = self.fg.g[ni].payload().fixed_location {
+                self.fg.g[ni].set_location(location);
+            }
+        }
+
+        self.group_namespaces();
+    }
+    /// Replaces nodes sharing a namespace prefix by a single node with the union of their edges
+    fn group_namespaces(&mut self) {
+        let depth = self.filter_settings.group_depth;
+        if depth == 0 {
+            return;
+        }
+
+        let mut groups = BTreeMap::<String, Vec<NodeIndex<u32>>>::new();
+        for ni in self.fg.g.node_indices() {
+            let parts = self.fg.g[ni].payload().name.split('.').collect::<Vec<_>>();
+            if parts.len() <= depth {
+                continue;
+            }
+            let group = parts[..depth].join(".");
+            if self.filter_settings.expanded_groups.contains(&group) {
+                continue;
+            }
+            groups.entry(group).or_default().push(ni);
+        }
+        groups.retain(|_, members| members.len() > 1);
+
+        let mut group_of = HashMap::new();
+        for (group, members) in groups {
+            let cnt = members.len() as f32;
+            let mut payload = NodePayload::from(&NodeData::placeholder(format!("{group}.* ({})", members.len())));
+            payload.const_type = format!("Namespace {group} with {} constants, select to expand", members.len());
+            payload.size = members.iter().map(|&ni| self.fg.g[ni].payload().size.powi(2)).sum::<f32>().sqrt();
+            payload.display_size = payload.size;
+            payload.color = members.iter().fold([0.; 3], |acc, &ni| {
+                let color = self.fg.g[ni].payload().color;
+                [0, 1, 2].map(|i| acc[i] + color[i] / cnt)
+            });
+            let state = self.group_states.get(&group).copied().unwrap_or_else(|| GroupState {
+                location: (members.iter().map(|&ni| self.fg.g[ni].location().to_vec2()).sum::<Vec2>() / cnt).to_pos2(),
+                vel: Vec2::ZERO,
+                comp_color: (members.iter().fold([0.; 3], |acc, &ni| {
+                    let color = self.fg.g[ni].payload().comp_color();
+                    [0, 1, 2].map(|i| acc[i] + color[i] / cnt)
+                }), 1.),
+                heat: members.iter().filter_map(|&ni| self.fg.g[ni].payload().heat).reduce(f32::max),
+            });
+            let location = state.location;
+            payload.vel = state.vel;
+            payload.comp_color = state.comp_color;
+            payload.heat = state.heat;
+            payload.group = Some(group);
+
+            let label = payload.name.clone();
+            let gni = self.fg.g.add_node(Node::new(payload).with_label(label));
+            self.fg.g[gni].bind(gni, location);
+            for ni in members {
+                group_of.insert(ni, gni);
+            }
+        }
+
+        let mut edge_weights = BTreeMap::new();
+        for ei in self.fg.g.edge_indices() {
+            let (start, end) = self.fg.g.edge_endpoints(ei).unwrap();
+            if !group_of.contains_key(&start) && !group_of.contains_key(&end) {
+                continue;
+            }
+            let start = group_of.get(&start).copied().unwrap_or(start);
+            let end = group_of.get(&end).copied().unwrap_or(end);
+            if start != end {
+                *edge_weights.entry((start, end)).or_insert(0) += self.fg.g[ei].payload().weight;
+            }
+        }
+
+        for &ni in group_of.keys() {
+            self.fg.g.remove_node(ni);
+        }
+        for ((start, end), weight) in edge_weights {
+            let ind = self.fg.g.add_edge(start, end, Edge::new(EdgePayload { weight, diff: None }));
+            self.fg.g.edge_weight_mut(ind).unwrap().bind(ind, 1);
+        }
+    }
+    fn clear_comparison(&mut self) {
+        // nodes are copied back from the filtered graph by index, so remove the added ones from both
+        clear_diff(&mut self.g.write().unwrap());
+        clear_diff(&mut self.fg);
+        *self.graph_diff.write().unwrap() = None;
+        // the cache refers to the removed nodes
+        self.coloring_cache = None;
     }
     fn save_viz(&self) -> StoredData {
         StoredData {
@@ -586,21 +2272,75 @@ impl MApp {
             force_settings: self.force_settings.clone(),
             g: self.g.read().unwrap().clone(),
             coloring_settings: self.coloring_settings.clone(),
+            display_settings: self.display_settings.clone(),
         }
     }
+    fn session(&self) -> Session {
+        let g = self.g.read().unwrap();
+        Session {
+            nodes: g.g.node_weights().map(|node| (node.payload().name.clone(), SessionNode::of(node.payload(), node.location()))).collect(),
+            force_settings: self.force_settings.clone(),
+            filter_settings: self.filter_settings.clone(),
+            coloring_settings: self.coloring_settings.clone(),
+            display_settings: self.display_settings.clone(),
+        }
+    }
+    fn restore_pending_session(&mut self, pending: PendingSession) {
+        if pending.key == self.session_key {
+            self.restore_session(pending.session);
+            return;
+        }
+        let Some(source) = pending.source else {
+            log::error!("the graph of the previous session was not saved");
+            return;
+        };
+        let (mut g, load_report) = match load_graph(source, false) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                log::error!("failed to load the graph of the previous session: {err}");
+                return;
+            }
+        };
+        let nis = g.g.node_indices().collect::<Vec<_>>();
+        pending.session.apply_nodes(&mut g, nis);
+        self.session_key = pending.key;
+        publish_graph(&self.g, &self.g_updated, |cur| *cur = g);
+        *self.load_report.write().unwrap() = load_report;
+        self.restore_session_settings(pending.session);
+    }
+    fn restore_session(&mut self, session: Session) {
+        let mut g = self.g.write().unwrap();
+        let nis = g.g.node_indices().collect::<Vec<_>>();
+        session.apply_nodes(&mut g, nis);
+        drop(g);
+        let visible = self.fg.g.node_indices().filter(|&ni| self.is_visible(ni)).collect::<Vec<_>>();
+        session.apply_nodes(&mut self.fg, visible);
+        self.restore_session_settings(session);
+    }
+    fn restore_session_settings(&mut self, session: Session) {
+        self.force_settings = session.force_settings;
+        self.filter_settings = session.filter_settings;
+        self.coloring_settings = session.coloring_settings;
+        self.display_settings = session.display_settings;
+        self.coloring_cache = None;
+        self.fit_until = None;
+        self.fit_whole_graph = true;
+    }
     fn load_stored_data(&mut self, data: StoredData) {
         *self.g.write().unwrap() = data.g;
         *self.g_updated.write().unwrap() = true;
         self.last_update = now();
+        self.time_accumulator = 0.;
         self.force_settings = data.force_settings;
         self.filter_settings = data.filter_settings;
         self.coloring_settings = data.coloring_settings;
-        *self.fit_to_screen.write().unwrap() = true;
+        self.display_settings = data.display_settings;
+        self.fit_whole_graph = true;
     }
 }
 
 impl App for MApp {
-    fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
         let mut data_to_load_write = self.data_to_load.write().unwrap();
         if let Some(data_to_load) = data_to_load_write.take() {
             drop(data_to_load_write);
@@ -610,46 +2350,369 @@ impl App for MApp {
             drop(data_to_load_write);
         }
         self.update_filter_graph();
+        if std::mem::take(&mut self.session_lookup) {
+            self.session_to_restore = frame.storage().and_then(|storage| read_session(storage, &self.session_key));
+        }
+        if ctx.input(|i| i.viewport().close_requested()) {
+            self.closing = true;
+        }
+        self.command_palette.update_shortcut(ctx);
+        self.update_history();
+        self.apply_display_settings();
+        self.update_highlight();
+        self.update_dragged(ctx);
         let ct = now();
-        let dt = (ct.clone() - self.last_update).as_secs_f32();
-        self.simulate_force_graph(dt.min(0.032));
+        self.time_accumulator += ct.saturating_sub(self.last_update).as_secs_f32();
         self.last_update = ct;
+        let fixed_dt = self.force_settings.fixed_dt;
+        let mut substeps = 0;
+        // don't spend time simulating a large graph before the user decides how to show it
+        let paused = self.large_graph_prompt.is_some();
+        while !paused && self.time_accumulator >= fixed_dt && substeps < self.force_settings.max_substeps {
+            self.simulate_force_graph(fixed_dt);
+            self.time_accumulator -= fixed_dt;
+            substeps += 1;
+        }
+        // drop the time we couldn't catch up with, so slow frames don't pile up
+        self.time_accumulator = self.time_accumulator.min(fixed_dt);
+        self.update_fit_until(ct);
         self.color_nodes();
         self.draw_ui(ctx);
+        // after the ui, so the changes made in it this frame are committed before the next undo
+        self.update_undo(ctx);
+    }
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        // don't overwrite the previous session before the user decides about it
+        if self.session_to_restore.is_some() {
+            return;
+        }
+        if self.closing {
+            // sessions are only for recovering from a crash, closing normally drops it
+            storage.set_string(&self.session_key, String::new());
+            return;
+        }
+        let last_key = storage.get_string(LAST_SESSION_KEY);
+        if last_key.as_ref() != Some(&self.session_key) {
+            // only the session of the last shown graph is kept
+            if let Some(last_key) = last_key.filter(|key| !key.is_empty()) {
+                storage.set_string(&last_key, String::new());
+                storage.set_string(&source_key(&last_key), String::new());
+            }
+            storage.set_string(&source_key(&self.session_key), graph_source(&self.g.read().unwrap()));
+            storage.set_string(LAST_SESSION_KEY, self.session_key.clone());
+        }
+        if !std::mem::take(&mut self.session_changed) {
+            return;
+        }
+        let session = serde_json::to_string(&self.session()).unwrap();
+        storage.set_string(&self.session_key, session.clone());
+        // the browser storage silently drops values over its quota
+        self.autosave_failed = storage.get_string(&self.session_key).as_ref() != Some(&session)
+            || storage.get_string(&source_key(&self.session_key)).map_or(true, |source| source.is_empty());
+    }
+    fn auto_save_interval(&self) -> Duration {
+        AUTOSAVE_INTERVAL
     }
 }
 
-fn load_graph(default_file_raw: String) -> G {
-    let nodes = serde_json::from_str::<Vec<NodeData>>(&default_file_raw).unwrap();
-    let mut sg = StableGraph::<_, _, Directed, _>::default();
+/// Newest version of the extracted data format this app understands
+const SUPPORTED_SCHEMA_VERSION: u32 = 1;
 
-    let spawn_radius = (nodes.len() as f32).sqrt() * 1000.;
+/// Extracted data, either the original bare list of constants or a versioned object
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExtractedData {
+    Versioned { version: u32, nodes: Vec<NodeData> },
+    Nodes(Vec<NodeData>),
+}
 
-    let nodes = nodes
-        .into_iter()
-        .map(|node| {
-            let ind =
-                sg.add_node(Node::new(NodePayload::from(&node)).with_label(node.name.clone()));
-            sg.node_weight_mut(ind)
-                .unwrap()
-                .bind(ind, random_location(spawn_radius));
+/// Problems found while loading extracted data
+#[derive(Clone, Default)]
+struct LoadReport {
+    /// References to constants missing from the data, as (source, missing target) names
+    unresolved: Vec<(String, String)>,
+    /// Version of data newer than supported, cleared once the user dismisses the warning
+    schema_warning: Option<u32>,
+}
 
-            (node.name.clone(), (ind, node))
+/// Builds the graph from extracted data in chunks, so that loading can report progress
+struct GraphLoader {
+    pending: std::vec::IntoIter<NodeData>,
+    total: usize,
+    spawn_radius: f32,
+    nodes: BTreeMap<String, (NodeIndex<u32>, NodeData)>,
+    g: G,
+    schema_version: u32,
+}
+
+impl GraphLoader {
+    fn new(raw: &str) -> serde_json::Result<Self> {
+        let (schema_version, nodes) = match serde_json::from_str::<ExtractedData>(raw)? {
+            ExtractedData::Versioned { version, nodes } => (version, nodes),
+            ExtractedData::Nodes(nodes) => (1, nodes),
+        };
+        Ok(Self {
+            schema_version,
+            total: nodes.len(),
+            spawn_radius: (nodes.len() as f32).sqrt() * 1000.,
+            pending: nodes.into_iter(),
+            nodes: BTreeMap::new(),
+            g: G::new(StableGraph::default()),
         })
-        .collect::<BTreeMap<String, (_, NodeData)>>();
+    }
+    /// Adds at most `chunk_size` nodes, returns whether all nodes are added
+    fn add_nodes(&mut self, chunk_size: usize) -> bool {
+        for node in self.pending.by_ref().take(chunk_size) {
+            let ind = self.g.g.add_node(Node::new(NodePayload::from(&node)).with_label(node.name.clone()));
+            self.g.g.node_weight_mut(ind)
+                .unwrap()
+                .bind(ind, random_location(self.spawn_radius));
+
+            self.nodes.insert(node.name.clone(), (ind, node));
+        }
+        self.pending.len() == 0
+    }
+    fn progress(&self) -> f32 {
+        if self.total == 0 {
+            return 1.;
+        }
+        1. - self.pending.len() as f32 / self.total as f32
+    }
+    fn finish(mut self, create_placeholders: bool) -> (G, LoadReport) {
+        self.add_nodes(usize::MAX);
+
+        let mut report = LoadReport {
+            schema_warning: (self.schema_version > SUPPORTED_SCHEMA_VERSION).then_some(self.schema_version),
+            ..Default::default()
+        };
+        let mut dangling = vec![];
+
+        // collapse duplicate references into a single weighted edge
+        let mut edge_weights = BTreeMap::new();
+        for (_, (ind, data)) in &self.nodes {
+            for reference in &data.references {
+                if let Some(node) = self.nodes.get(reference) {
+                    *edge_weights.entry((node.0, *ind)).or_insert(0) += 1;
+                } else {
+                    report.unresolved.push((data.name.clone(), reference.clone()));
+                    dangling.push((*ind, reference.clone()));
+                }
+            }
+        }
+
+        if create_placeholders {
+            let mut placeholders = BTreeMap::new();
+            for (ind, reference) in dangling {
+                let placeholder = *placeholders.entry(reference.clone()).or_insert_with(|| {
+                    let node = NodeData::placeholder(reference);
+                    let pind = self.g.g.add_node(Node::new(NodePayload::from(&node)).with_label(node.name.clone()));
+                    self.g.g.node_weight_mut(pind)
+                        .unwrap()
+                        .bind(pind, random_location(self.spawn_radius));
+                    pind
+                });
+                *edge_weights.entry((placeholder, ind)).or_insert(0) += 1;
+            }
+        }
+
+        for ((start, end), weight) in edge_weights {
+            let ind = self.g.g.add_edge(start, end, Edge::new(EdgePayload { weight, diff: None }));
+            self.g.g.edge_weight_mut(ind).unwrap().bind(ind, 1);
+        }
+
+        (self.g, report)
+    }
+}
+
+fn load_graph(default_file_raw: String, create_placeholders: bool) -> serde_json::Result<(G, LoadReport)> {
+    Ok(GraphLoader::new(&default_file_raw)?.finish(create_placeholders))
+}
+
+/// Adds the nodes and edges of `other` to `g`, constants with the same name are merged
+fn merge_graph(g: &mut G, other: G) {
+    let mut indices = g.g.node_indices().map(|ni| (g.g[ni].payload().name.clone(), ni)).collect::<HashMap<_, _>>();
+
+    let mut mapping = HashMap::new();
+    for oni in other.g.node_indices() {
+        let node = &other.g[oni];
+        let ni = match indices.get(&node.payload().name) {
+            Some(&ni) => {
+                g.g[ni].payload_mut().source_count += 1;
+                ni
+            }
+            None => {
+                let ni = g.g.add_node(Node::new(node.payload().clone()).with_label(node.payload().name.clone()));
+                g.g[ni].bind(ni, node.location());
+                indices.insert(node.payload().name.clone(), ni);
+                ni
+            }
+        };
+        mapping.insert(oni, ni);
+    }
+
+    for ei in other.g.edge_indices() {
+        let (start, end) = other.g.edge_endpoints(ei).unwrap();
+        let (start, end) = (mapping[&start], mapping[&end]);
+        if g.g.find_edge(start, end).is_none() {
+            let ind = g.g.add_edge(start, end, other.g[ei].clone());
+            g.g.edge_weight_mut(ind).unwrap().bind(ind, 1);
+        }
+    }
+}
+
+/// Sets up a font that supports math characters
+fn setup_fonts(ctx: &egui::Context) {
+    let mut fonts = egui::FontDefinitions::default();
+    fonts.font_data.insert("noto_sans_math".into(), egui::FontData::from_static(include_bytes!("../static/NotoSansMath-Regular.ttf")));
+    fonts.families.entry(egui::FontFamily::Proportional).or_default().insert(0, "noto_sans_math".into());
+    // node labels are monospace, keep its own glyphs and only fall back to math for the missing ones
+    fonts.families.entry(egui::FontFamily::Monospace).or_default().push("noto_sans_math".into());
+    ctx.set_fonts(fonts);
+}
+
+/// Loads extracted data and lays it out without any UI, e.g. for generating images in scripts
+///
+/// Fails when `raw` is not valid extracted data.
+pub fn layout_graph(raw: &str, settings: &ForceSettings, iterations: usize) -> serde_json::Result<G> {
+    let (mut g, _) = load_graph(raw.to_string(), false)?;
+    let frozen = HashSet::new();
+    for _ in 0..iterations {
+        force::simulate_force_graph(&mut g, settings, &frozen, settings.fixed_dt);
+    }
+    Ok(g)
+}
+
+/// Loads the graph in chunks, yielding in between and publishing the progress
+///
+/// Invalid data is logged and clears the progress.
+async fn load_graph_async(raw: String, create_placeholders: bool, progress: Arc<RwLock<Option<f32>>>) -> Option<(G, LoadReport)> {
+    const CHUNK_SIZE: usize = 500;
+
+    // let the progress show up before parsing, which takes a while on its own
+    *progress.write().unwrap() = Some(0.);
+    yield_now().await;
+    let mut loader = match GraphLoader::new(&raw) {
+        Ok(loader) => loader,
+        Err(err) => {
+            log::error!("failed to parse the extracted data: {err}");
+            *progress.write().unwrap() = None;
+            return None;
+        }
+    };
+    while !loader.add_nodes(CHUNK_SIZE) {
+        *progress.write().unwrap() = Some(loader.progress());
+        yield_now().await;
+    }
+    *progress.write().unwrap() = Some(1.);
+    yield_now().await;
+    Some(loader.finish(create_placeholders))
+}
+
+/// Changes the source graph from a loading task
+///
+/// The updated flag is set while holding the graph, the ui checks it under the same lock before copying its nodes back by index.
+fn publish_graph(g: &RwLock<G>, g_updated: &RwLock<bool>, update: impl FnOnce(&mut G)) {
+    let mut g = g.write().unwrap();
+    update(&mut g);
+    *g_updated.write().unwrap() = true;
+}
 
-    for (_, (ind, data)) in &nodes {
-        for reference in &data.references {
-            if let Some(node) = nodes.get(reference) {
-                let ind = sg.add_edge(node.0, *ind, Edge::new(()));
-                sg.edge_weight_mut(ind).unwrap().bind(ind, 1);
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(callback: &js_sys::Function, ms: i32) -> i32;
+}
+
+/// Gives the browser a chance to draw a frame, waking the task right away would run it again before anything is drawn
+#[cfg(target_arch = "wasm32")]
+async fn yield_now() {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        set_timeout(&resolve, 0);
+    });
+    wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|cx| {
+        if yielded {
+            return std::task::Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        std::task::Poll::Pending
+    })
+    .await
+}
+
+/// Smallest canvas rect containing all nodes with their radius
+fn graph_bounds(g: &G) -> Option<egui::Rect> {
+    g.g.node_weights()
+        .map(|node| egui::Rect::from_center_size(node.location(), Vec2::splat(2. * NODE_RADIUS * node.payload().display_size)))
+        .reduce(|a, b| a.union(b))
+}
+
+/// Index of the weakly connected component of every node
+fn weak_components<N, E>(g: &StableGraph<N, E, Directed, u32>) -> HashMap<NodeIndex<u32>, usize> {
+    let mut res = HashMap::new();
+    let mut component_cnt = 0;
+    for start in g.node_indices() {
+        if res.contains_key(&start) {
+            continue;
+        }
+        res.insert(start, component_cnt);
+        let mut stack = vec![start];
+        while let Some(cur) = stack.pop() {
+            for oni in g.neighbors_undirected(cur) {
+                if !res.contains_key(&oni) {
+                    res.insert(oni, component_cnt);
+                    stack.push(oni);
+                }
             }
         }
+        component_cnt += 1;
     }
+    res
+}
+
+/// Edges closing a cycle in a depth first search, removing them leaves the graph acyclic
+fn back_edges<N, E>(g: &StableGraph<N, E, Directed, u32>) -> HashSet<(NodeIndex<u32>, NodeIndex<u32>)> {
+    const ON_STACK: u8 = 1;
+    const DONE: u8 = 2;
 
-    let g = G::new(sg);
+    let mut state = HashMap::new();
+    let mut res = HashSet::new();
+
+    for start in g.node_indices() {
+        if state.contains_key(&start) {
+            continue;
+        }
+        state.insert(start, ON_STACK);
+        let mut stack = vec![(start, g.neighbors(start).collect::<Vec<_>>())];
+        while let Some((cur, children)) = stack.last_mut() {
+            let cur = *cur;
+            match children.pop() {
+                Some(next) => match state.get(&next) {
+                    Some(&ON_STACK) => {
+                        res.insert((cur, next));
+                    }
+                    Some(_) => {}
+                    None => {
+                        state.insert(next, ON_STACK);
+                        stack.push((next, g.neighbors(next).collect()));
+                    }
+                },
+                None => {
+                    state.insert(cur, DONE);
+                    stack.pop();
+                }
+            }
+        }
+    }
 
-    g
+    res
 }
 
 fn random_location(size: f32) -> Pos2 {
@@ -659,16 +2722,34 @@ fn random_location(size: f32) -> Pos2 {
     pos
 }
 
+/// Decompresses gzipped data, plain data is returned as is
+pub fn decode_graph_raw(data_raw: Vec<u8>) -> std::io::Result<String> {
+    const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+    if data_raw.starts_with(&GZIP_MAGIC) {
+        let mut decoded = String::new();
+        GzDecoder::new(data_raw.as_slice()).read_to_string(&mut decoded)?;
+        Ok(decoded)
+    } else {
+        String::from_utf8(data_raw).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+}
+
 pub async fn read_graph_file_dialog() -> Option<String> {
     let Some(file_handle) = AsyncFileDialog::new()
-        .add_filter("Json", &["json"])
+        .add_filter("Json", &["json", "gz"])
         .pick_file()
         .await
     else {
         return None;
     };
     let data_raw = file_handle.read().await;
-    Some(String::from_utf8(data_raw).unwrap())
+    match decode_graph_raw(data_raw) {
+        Ok(decoded) => Some(decoded),
+        Err(err) => {
+            log::error!("failed to read {}: {err}", file_handle.file_name());
+            None
+        }
+    }
 }
 
 pub async fn read_raw_stored_data_file_dialog() -> Option<String> {
@@ -684,10 +2765,11 @@ pub async fn read_raw_stored_data_file_dialog() -> Option<String> {
 }
 
 
-pub async fn read_graph_url(url: &str) -> Result<String, reqwest::Error> {
+pub async fn read_graph_url(url: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
     let resp = reqwest::get(url).await?;
     resp.error_for_status_ref()?;
-    resp.text().await
+    let data_raw = resp.bytes().await?;
+    Ok(decode_graph_raw(data_raw.to_vec())?)
 }
 
 pub async fn read_dep_extractor() -> Result<String, reqwest::Error> {
@@ -696,18 +2778,253 @@ pub async fn read_dep_extractor() -> Result<String, reqwest::Error> {
     resp.text().await
 }
 
+#[cfg(target_arch = "wasm32")]
 fn spawn_local<F>(future: F)
 where
     F: Future<Output = ()> + 'static,
 {
-    #[cfg(target_arch = "wasm32")]
     wasm_bindgen_futures::spawn_local(future);
-    #[cfg(not(target_arch = "wasm32"))]
-    {
+}
+
+/// Runs the future on its own thread, so the ui keeps drawing while files are opened and loaded
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_local<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    std::thread::spawn(move || {
         tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .unwrap()
             .block_on(future);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_json(name: &str, references: &[&str]) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "references": references,
+            "constCategory": "Theorem",
+            "constType": "Prop",
+        })
+    }
+
+    fn find_node(g: &G, name: &str) -> NodeIndex<u32> {
+        g.g.node_indices().find(|&ni| g.g[ni].payload().name == name).unwrap()
+    }
+
+    #[test]
+    fn duplicate_references_become_one_weighted_edge() {
+        let raw = serde_json::json!([node_json("a", &[]), node_json("b", &["a", "a"])]).to_string();
+        let (g, report) = load_graph(raw, false).unwrap();
+
+        assert!(report.unresolved.is_empty());
+        assert_eq!(g.g.edge_count(), 1);
+        let ei = g.g.find_edge(find_node(&g, "a"), find_node(&g, "b")).unwrap();
+        assert_eq!(g.g[ei].payload().weight, 2);
+    }
+
+    #[test]
+    fn gzipped_data_loads_like_plain_data() {
+        use std::io::Write;
+
+        let raw = serde_json::json!([node_json("a", &[]), node_json("b", &["a"])]).to_string();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(raw.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let decoded = decode_graph_raw(compressed).unwrap();
+        assert_eq!(decoded, raw);
+        assert_eq!(decode_graph_raw(raw.clone().into_bytes()).unwrap(), raw);
+
+        let (g, _) = load_graph(decoded, false).unwrap();
+        assert_eq!(g.g.node_count(), 2);
+        assert!(g.g.find_edge(find_node(&g, "a"), find_node(&g, "b")).is_some());
+    }
+
+    #[test]
+    fn invalid_data_is_an_error() {
+        assert!(load_graph("{ \"nodes\": ".to_string(), false).is_err());
+    }
+
+    #[test]
+    fn corrupt_archive_is_an_error() {
+        assert!(decode_graph_raw(vec![0x1f, 0x8b, 0x08, 0x00, 0xde, 0xad]).is_err());
+    }
+
+    #[test]
+    fn hsl_round_trip() {
+        for c in [[0.9, 0.1, 0.1], [0.2, 0.6, 0.3], [0.1, 0.2, 0.55], [0.5, 0.5, 0.5], [0.6, 0.1, 0.5], [0., 0., 0.]] {
+            let back = hsl_to_rgb(rgb_to_hsl(c));
+            assert!((0..3).all(|i| (back[i] - c[i]).abs() < 1e-4), "{c:?} came back as {back:?}");
+        }
+    }
+
+    #[test]
+    fn dark_mode_keeps_colors_apart() {
+        let distance = |a: Color32, b: Color32| (0..3).map(|i| (a[i] as i32 - b[i] as i32).abs()).sum::<i32>();
+        for (a, b) in [
+            ([0.5, 0.1, 0.1], [0.1, 0.1, 0.5]),
+            ([0.2, 0.2, 0.6], [0.1, 0.1, 0.3]),
+            ([0.6, 0.4, 0.1], [0.1, 0.5, 0.2]),
+        ] {
+            let (light, dark) = (distance(theme_color(a, false), theme_color(b, false)), distance(theme_color(a, true), theme_color(b, true)));
+            assert!(dark > 40, "{a:?} and {b:?} are too close in dark mode: {dark} (light mode {light})");
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_colors() {
+        assert_eq!(seeded_colors(42, 10), seeded_colors(42, 10));
+        assert_ne!(seeded_colors(42, 10), seeded_colors(43, 10));
+        // more nodes don't change the colors of the first ones
+        assert_eq!(seeded_colors(42, 5), seeded_colors(42, 10)[..5]);
+    }
+
+    #[test]
+    fn bare_array_is_the_first_schema_version() {
+        let raw = serde_json::json!([node_json("a", &[])]).to_string();
+        let (g, report) = load_graph(raw, false).unwrap();
+        assert_eq!(g.g.node_count(), 1);
+        assert_eq!(report.schema_warning, None);
+    }
+
+    #[test]
+    fn versioned_object_is_loaded() {
+        let raw = serde_json::json!({ "version": SUPPORTED_SCHEMA_VERSION, "nodes": [node_json("a", &[]), node_json("b", &["a"])] }).to_string();
+        let (g, report) = load_graph(raw, false).unwrap();
+        assert_eq!(g.g.node_count(), 2);
+        assert_eq!(g.g.edge_count(), 1);
+        assert_eq!(report.schema_warning, None);
+    }
+
+    #[test]
+    fn newer_version_warns() {
+        let version = SUPPORTED_SCHEMA_VERSION + 1;
+        let raw = serde_json::json!({ "version": version, "nodes": [node_json("a", &[])] }).to_string();
+        let (g, report) = load_graph(raw, false).unwrap();
+        assert_eq!(g.g.node_count(), 1);
+        assert_eq!(report.schema_warning, Some(version));
+    }
+
+    #[test]
+    fn clearing_a_comparison_recolors_the_remaining_nodes() {
+        let (g, report) = load_graph(serde_json::json!([node_json("a", &[]), node_json("b", &["a"])]).to_string(), false).unwrap();
+        let mut app = MApp::with_graph(g, report);
+        app.update_filter_graph();
+        app.color_nodes();
+
+        let other = serde_json::json!([node_json("a", &[]), node_json("b", &["a"]), node_json("c", &["b"]), node_json("d", &["c"])]);
+        let (other, _) = load_graph(other.to_string(), false).unwrap();
+        *app.graph_diff.write().unwrap() = Some(diff_graph(&mut app.g.write().unwrap(), other));
+        *app.g_updated.write().unwrap() = true;
+        app.update_filter_graph();
+        app.color_nodes();
+        assert_eq!(app.fg.g.node_count(), 4);
+
+        app.clear_comparison();
+        app.update_filter_graph();
+        let ni = find_node(&app.fg, "a");
+        app.fg.g[ni].set_selected(true);
+        app.color_nodes();
+        assert_eq!(app.fg.g.node_count(), 2);
+        assert!(app.fg.g.node_weights().all(|node| node.payload().diff.is_none()));
+    }
+
+    #[test]
+    fn bounds_include_node_radii() {
+        let raw = serde_json::json!([node_json("a", &[]), node_json("b", &[]), node_json("c", &[])]).to_string();
+        let (mut g, _) = load_graph(raw, false).unwrap();
+        for (name, location, display_size) in [("a", Pos2::new(0., 0.), 1.), ("b", Pos2::new(100., 0.), 3.), ("c", Pos2::new(0., -50.), 0.5)] {
+            let ni = find_node(&g, name);
+            g.g[ni].set_location(location);
+            g.g[ni].payload_mut().display_size = display_size;
+        }
+
+        let bounds = graph_bounds(&g).unwrap();
+        let expected = egui::Rect::from_min_max(Pos2::new(-NODE_RADIUS, -50. - 0.5 * NODE_RADIUS), Pos2::new(100. + 3. * NODE_RADIUS, 3. * NODE_RADIUS));
+        assert!((bounds.min - expected.min).length() < 1e-4 && (bounds.max - expected.max).length() < 1e-4, "{bounds:?} != {expected:?}");
+        assert!(graph_bounds(&G::new(StableGraph::default())).is_none());
+    }
+
+    /// Rewrites a stored visualization into the format saved before the edges were weighted and the shapes grew
+    fn to_baseline_format(value: &mut serde_json::Value) {
+        const ADDED_KEYS: [&str; 20] = [
+            "weight", "diff", "heat", "always_label", "dimmed", "in_cycle", "is_root", "is_leaf", "duplicate", "display_size",
+            "pinned", "fixed_location", "color_locked", "cycle", "group", "source_count", "source_file", "source_line", "source_url", "display_settings",
+        ];
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut keys = map.keys().map(String::as_str).collect::<Vec<_>>();
+                keys.sort();
+                if keys == ["diff", "weight"] {
+                    *value = serde_json::Value::Null;
+                    return;
+                }
+                for key in ADDED_KEYS {
+                    map.remove(key);
+                }
+                map.values_mut().for_each(to_baseline_format);
+            }
+            serde_json::Value::Array(values) => values.iter_mut().for_each(to_baseline_format),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn baseline_visualization_loads() {
+        let (g, report) = load_graph(serde_json::json!([node_json("a", &[]), node_json("b", &["a"])]).to_string(), false).unwrap();
+        let app = MApp::with_graph(g, report);
+        let mut value = serde_json::to_value(app.save_viz()).unwrap();
+        to_baseline_format(&mut value);
+        assert!(!value.to_string().contains("weight"));
+
+        let stored = serde_json::from_value::<StoredData>(value).unwrap();
+        assert_eq!(stored.g.g.node_count(), 2);
+        let ei = stored.g.g.find_edge(find_node(&stored.g, "a"), find_node(&stored.g, "b")).unwrap();
+        assert_eq!(stored.g.g[ei].payload().weight, 1);
+        assert!(stored.g.g[ei].payload().diff.is_none());
+    }
+
+    #[test]
+    fn headless_layout_settles_apart() {
+        let raw = serde_json::json!([node_json("a", &[]), node_json("b", &["a"]), node_json("c", &["a", "b"])]).to_string();
+        let g = layout_graph(&raw, &ForceSettings::default(), 100).unwrap();
+
+        assert_eq!(g.g.node_count(), 3);
+        let locations = g.g.node_weights().map(|node| node.location()).collect::<Vec<_>>();
+        assert!(locations.iter().all(|pos| pos.x.is_finite() && pos.y.is_finite()), "{locations:?}");
+        assert!(locations[0] != locations[1] && locations[1] != locations[2], "{locations:?}");
+        assert!(layout_graph("not extracted data", &ForceSettings::default(), 1).is_err());
+    }
+
+    #[test]
+    fn session_graph_is_rebuilt_from_its_source() {
+        let raw = serde_json::json!([node_json("a", &[]), node_json("b", &["a", "a"]), node_json("c", &["a", "b"])]).to_string();
+        let (g, _) = load_graph(raw, false).unwrap();
+
+        let (rebuilt, _) = load_graph(graph_source(&g), false).unwrap();
+        assert_eq!(session_key(&rebuilt), session_key(&g));
+        assert_eq!(rebuilt.g.edge_count(), g.g.edge_count());
+        let ei = rebuilt.g.find_edge(find_node(&rebuilt, "a"), find_node(&rebuilt, "b")).unwrap();
+        assert_eq!(rebuilt.g[ei].payload().weight, 2);
+        // keys are persisted, so they must not change between builds
+        let (single, _) = load_graph(serde_json::json!([node_json("a", &[])]).to_string(), false).unwrap();
+        assert_eq!(session_key(&single), format!("{AUTOSAVE_KEY}/089be207b544f1e4"));
+    }
+
+    #[test]
+    fn monospace_labels_have_math_glyphs() {
+        let ctx = egui::Context::default();
+        setup_fonts(&ctx);
+        // fonts are only built at the start of a frame
+        let _ = ctx.run(Default::default(), |_| {});
+
+        assert!(ctx.fonts(|fonts| fonts.has_glyphs(&egui::FontId::monospace(12.), "⊢∘")));
     }
 }
@@ -2,13 +2,38 @@ use std::f32::consts::PI;
 
 use egui::{
     epaint::{CircleShape, TextShape},
-    FontFamily, FontId, Pos2, Shape, Stroke, Vec2,
+    Color32, FontFamily, FontId, Pos2, Shape, Stroke, Vec2,
 };
-use egui_graphs::{DisplayNode, DrawContext, NodeProps};
+use egui_graphs::{DisplayNode, DrawContext, Metadata, NodeProps};
 use petgraph::{stable_graph::IndexType, EdgeType};
 use serde::{Deserialize, Serialize};
 
-use crate::{col_ft, ConstCategory, NodePayload};
+use crate::{theme_color, viridis, ConstCategory, DiffStatus, DisplaySettings, NodePayload, NODE_RADIUS};
+
+const STAR_POINTS: usize = 5;
+const STAR_INNER_RATIO: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum NodeShapeKind {
+    #[default]
+    Circle,
+    Triangle,
+    Square,
+    Pentagon,
+    Hexagon,
+    Star,
+}
+
+impl NodeShapeKind {
+    pub const ALL: [NodeShapeKind; 6] = [
+        NodeShapeKind::Circle,
+        NodeShapeKind::Triangle,
+        NodeShapeKind::Square,
+        NodeShapeKind::Pentagon,
+        NodeShapeKind::Hexagon,
+        NodeShapeKind::Star,
+    ];
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NodeShape {
@@ -22,6 +47,22 @@ pub struct NodeShape {
     /// Shape defined property
     pub radius: f32,
     color: [f32; 3],
+    #[serde(default)]
+    heat: Option<f32>,
+    #[serde(default)]
+    diff: Option<DiffStatus>,
+    #[serde(default)]
+    always_label: bool,
+    #[serde(default)]
+    dimmed: bool,
+    #[serde(default)]
+    in_cycle: bool,
+    #[serde(default)]
+    is_root: bool,
+    #[serde(default)]
+    is_leaf: bool,
+    #[serde(default)]
+    duplicate: bool,
 }
 
 impl From<NodeProps<NodePayload>> for NodeShape {
@@ -31,9 +72,17 @@ impl From<NodeProps<NodePayload>> for NodeShape {
             selected: node_props.selected,
             name: node_props.payload.name,
 
-            radius: 10. * node_props.payload.size,
+            radius: NODE_RADIUS * node_props.payload.display_size,
             color: node_props.payload.color,
+            heat: node_props.payload.heat,
+            diff: node_props.payload.diff,
             const_type: node_props.payload.const_category,
+            always_label: node_props.payload.always_label,
+            dimmed: node_props.payload.dimmed,
+            in_cycle: node_props.payload.cycle.is_some(),
+            is_root: node_props.payload.is_root,
+            is_leaf: node_props.payload.is_leaf,
+            duplicate: node_props.payload.source_count > 1,
         }
     }
 }
@@ -48,23 +97,51 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType> DisplayNode<NodePayload, E, Ty, Ix>
     }
 
     fn shapes(&mut self, ctx: &DrawContext) -> Vec<Shape> {
+        self.draw(&ctx.ctx, &ctx.meta)
+    }
+
+    fn update(&mut self, state: &NodeProps<NodePayload>) {
+        self.pos = state.location;
+        self.pos = state.location;
+        self.selected = state.selected;
+        self.name = state.payload.name.clone();
+        self.radius = NODE_RADIUS * state.payload.display_size;
+        self.always_label = state.payload.always_label;
+        self.dimmed = state.payload.dimmed;
+        self.in_cycle = state.payload.cycle.is_some();
+        self.is_root = state.payload.is_root;
+        self.is_leaf = state.payload.is_leaf;
+        self.duplicate = state.payload.source_count > 1;
+        self.color = state.payload.comp_color();
+        self.heat = state.payload.heat;
+        self.diff = state.payload.diff;
+    }
+}
+
+impl NodeShape {
+    /// Shapes of the node on the screen, also used to redraw emphasized nodes on top
+    pub fn draw(&self, ctx: &egui::Context, meta: &Metadata) -> Vec<Shape> {
         let mut res = Vec::with_capacity(2);
 
         let is_interacted = self.selected;
 
         let style = match is_interacted {
-            true => ctx.ctx.style().visuals.widgets.active,
-            false => ctx.ctx.style().visuals.widgets.inactive,
+            true => ctx.style().visuals.widgets.active,
+            false => ctx.style().visuals.widgets.inactive,
         };
-        let color = if ctx.ctx.style().visuals.dark_mode {
-            col_ft(self.color.map(|x| 1. - x))
-        } else {
-            col_ft(self.color.map(|x| x.sqrt()))
+        let display_settings = DisplaySettings::load_from_ctx(ctx);
+        let mut color = match (display_settings.show_diff, self.diff, self.heat) {
+            (true, Some(diff), _) => diff.color(),
+            (_, _, Some(heat)) => viridis(heat),
+            _ => theme_color(self.color, ctx.style().visuals.dark_mode),
         };
+        if self.dimmed {
+            color = color.gamma_multiply(0.2);
+        }
         let text_color = style.text_color();
 
-        let center = ctx.meta.canvas_to_screen_pos(self.pos);
-        let radius = ctx.meta.canvas_to_screen_size(self.radius);
+        let center = meta.canvas_to_screen_pos(self.pos);
+        let radius = meta.canvas_to_screen_size(self.radius);
         let get_n_polygon = |n: usize| {
             let step = 2. * PI / n as f32;
             (0..n)
@@ -76,22 +153,64 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType> DisplayNode<NodePayload, E, Ty, Ix>
                 .collect::<Vec<_>>()
         };
         let no_stroke = Stroke::new(0., color);
-        let shape = match self.const_type {
-            ConstCategory::Theorem => Shape::convex_polygon(get_n_polygon(5), color, no_stroke),
-            ConstCategory::Definition => Shape::convex_polygon(get_n_polygon(3), color, no_stroke),
-            ConstCategory::Axiom => CircleShape {
+        let shape = match display_settings.node_shapes.get(&self.const_type).copied().unwrap_or_default() {
+            NodeShapeKind::Circle => CircleShape {
                 center,
                 radius,
                 fill: color,
                 stroke: Stroke::default(),
             }
             .into(),
-            ConstCategory::Other => Shape::convex_polygon(get_n_polygon(4), color, no_stroke),
+            NodeShapeKind::Triangle => Shape::convex_polygon(get_n_polygon(3), color, no_stroke),
+            NodeShapeKind::Square => Shape::convex_polygon(get_n_polygon(4), color, no_stroke),
+            NodeShapeKind::Pentagon => Shape::convex_polygon(get_n_polygon(5), color, no_stroke),
+            NodeShapeKind::Hexagon => Shape::convex_polygon(get_n_polygon(6), color, no_stroke),
+            NodeShapeKind::Star => {
+                // star is concave, so draw it as an inner polygon with a triangle on each side
+                let inner_radius = radius * STAR_INNER_RATIO;
+                let step = PI / STAR_POINTS as f32;
+                let point = |i: usize, r: f32| Pos2::from(center + Vec2::angled(i as f32 * step) * r);
+                let mut shapes = vec![Shape::convex_polygon(
+                    (0..STAR_POINTS).map(|i| point(2 * i + 1, inner_radius)).collect(),
+                    color,
+                    no_stroke,
+                )];
+                for i in 0..STAR_POINTS {
+                    shapes.push(Shape::convex_polygon(
+                        vec![point(2 * i + 2 * STAR_POINTS - 1, inner_radius), point(2 * i, radius), point(2 * i + 1, inner_radius)],
+                        color,
+                        no_stroke,
+                    ));
+                }
+                Shape::Vec(shapes)
+            }
         };
 
         res.push(shape.into());
 
-        let galley = ctx.ctx.fonts(|f| {
+        if self.in_cycle {
+            res.push(Shape::circle_stroke(center, radius * 1.2, Stroke::new(2., Color32::from_rgb(220, 40, 40))));
+        }
+        if display_settings.highlight_roots_leaves {
+            if self.is_root {
+                res.push(Shape::circle_stroke(center, radius * 1.35, Stroke::new(2., Color32::GOLD)));
+            }
+            if self.is_leaf {
+                res.push(Shape::circle_stroke(center, radius * 1.5, Stroke::new(2., Color32::LIGHT_BLUE)));
+            }
+        }
+
+        if display_settings.highlight_duplicates && self.duplicate {
+            res.push(Shape::circle_stroke(center, radius * 1.65, Stroke::new(2., Color32::from_rgb(190, 80, 220))));
+        }
+
+        // skip labels of small nodes, text layout is expensive and clutters dense graphs
+        let is_hovered = ctx.pointer_hover_pos().is_some_and(|pos| is_inside_circle(center, radius, pos));
+        if radius < display_settings.label_min_radius && !is_interacted && !is_hovered && !self.always_label {
+            return res;
+        }
+
+        let galley = ctx.fonts(|f| {
             f.layout_no_wrap(
                 self.name.clone(),
                 FontId::new(radius, FontFamily::Monospace),
@@ -107,14 +226,6 @@ impl<E: Clone, Ty: EdgeType, Ix: IndexType> DisplayNode<NodePayload, E, Ty, Ix>
 
         res
     }
-
-    fn update(&mut self, state: &NodeProps<NodePayload>) {
-        self.pos = state.location;
-        self.pos = state.location;
-        self.selected = state.selected;
-        self.name = state.payload.name.clone();
-        self.color = state.payload.comp_color();
-    }
 }
 
 fn closest_point_on_circle(center: Pos2, radius: f32, dir: Vec2) -> Pos2 {
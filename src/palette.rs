@@ -0,0 +1,155 @@
+use egui::{Key, Modifiers};
+use petgraph::graph::NodeIndex;
+
+/// Maximal number of matches listed in the palette
+const MAX_RESULTS: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    FitToScreen,
+    SaveVisualization,
+    ToggleDarkMode,
+    RandomizeColors,
+}
+
+impl Command {
+    pub const ALL: [Command; 4] = [
+        Command::FitToScreen,
+        Command::SaveVisualization,
+        Command::ToggleDarkMode,
+        Command::RandomizeColors,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Command::FitToScreen => "Fit to screen",
+            Command::SaveVisualization => "Save visualization",
+            Command::ToggleDarkMode => "Toggle dark mode",
+            Command::RandomizeColors => "Randomize colors",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaletteItem {
+    Command(Command),
+    Node(NodeIndex<u32>),
+}
+
+/// Popup for running commands and jumping to nodes by typing
+#[derive(Default)]
+pub struct CommandPalette {
+    open: bool,
+    query: String,
+    selected: usize,
+}
+
+impl CommandPalette {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens or closes the palette on `Ctrl+P`, unless another widget takes the keyboard
+    pub fn update_shortcut(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() && !self.open {
+            return;
+        }
+        if ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::P)) {
+            self.open = !self.open;
+            self.query.clear();
+            self.selected = 0;
+        }
+    }
+
+    /// Shows the palette, returns the item chosen by the user
+    pub fn show<'a>(&mut self, ctx: &egui::Context, nodes: impl Iterator<Item = (NodeIndex<u32>, &'a str)>) -> Option<PaletteItem> {
+        if !self.open {
+            return None;
+        }
+
+        let mut items = Command::ALL
+            .iter()
+            .map(|&command| (PaletteItem::Command(command), command.label()))
+            .chain(nodes.map(|(ni, name)| (PaletteItem::Node(ni), name)))
+            .filter_map(|(item, label)| Some((fuzzy_score(&self.query, label)?, item, label)))
+            .collect::<Vec<_>>();
+        items.sort_by_key(|&(score, _, label)| (std::cmp::Reverse(score), label.len()));
+        items.truncate(MAX_RESULTS);
+
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                i.key_pressed(Key::Enter),
+                i.consume_key(Modifiers::NONE, Key::Escape),
+            )
+        });
+        if down {
+            self.selected += 1;
+        }
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        self.selected = self.selected.min(items.len().saturating_sub(1));
+
+        let mut chosen = None;
+        egui::Window::new("Command palette")
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0., 40.))
+            .show(ctx, |ui| {
+                let response = ui.add(egui::TextEdit::singleline(&mut self.query).hint_text("Type a command or constant name"));
+                response.request_focus();
+                if response.changed() {
+                    self.selected = 0;
+                }
+                for (i, &(_, item, label)) in items.iter().enumerate() {
+                    let text = match item {
+                        PaletteItem::Command(_) => format!("> {label}"),
+                        PaletteItem::Node(_) => label.to_string(),
+                    };
+                    if ui.selectable_label(i == self.selected, text).clicked() {
+                        chosen = Some(item);
+                    }
+                }
+            });
+
+        if enter {
+            chosen = items.get(self.selected).map(|&(_, item, _)| item);
+        }
+        if chosen.is_some() || escape {
+            self.open = false;
+        }
+        chosen
+    }
+}
+
+/// Score of `candidate` containing the characters of `query` in order, ignoring case, `None` if it doesn't
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let mut score = 0;
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut prev_matched = false;
+    let mut prev_char = None;
+    for c in candidate.chars().flat_map(char::to_lowercase) {
+        let Some(&q) = query.peek() else {
+            break;
+        };
+        if c == q {
+            query.next();
+            score += 1;
+            // consecutive characters and starts of words or namespaces count more
+            if prev_matched {
+                score += 3;
+            }
+            if prev_char.map_or(true, |p: char| p == '.' || p == '_' || p == ' ') {
+                score += 2;
+            }
+            prev_matched = true;
+        } else {
+            prev_matched = false;
+        }
+        prev_char = Some(c);
+    }
+    query.peek().is_none().then_some(score)
+}
@@ -0,0 +1,186 @@
+use std::cmp::Ordering;
+
+use egui::{Key, Modifiers};
+use petgraph::{graph::NodeIndex, Direction};
+
+use crate::G;
+
+const ROW_HEIGHT: f32 = 18.;
+const COLUMN_WIDTHS: [f32; 5] = [260., 90., 90., 90., 400.];
+/// Longer types are cut, the full type is in the selection window
+const MAX_TYPE_LEN: usize = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableColumn {
+    Name,
+    Category,
+    /// Number of constants referencing the node
+    ReferencedBy,
+    /// Number of constants the node references
+    References,
+    Type,
+}
+
+impl TableColumn {
+    const ALL: [TableColumn; 5] = [
+        TableColumn::Name,
+        TableColumn::Category,
+        TableColumn::ReferencedBy,
+        TableColumn::References,
+        TableColumn::Type,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            TableColumn::Name => "Name",
+            TableColumn::Category => "Category",
+            TableColumn::ReferencedBy => "Referenced by",
+            TableColumn::References => "References",
+            TableColumn::Type => "Type",
+        }
+    }
+}
+
+struct Row {
+    ni: NodeIndex<u32>,
+    name: String,
+    category: String,
+    referenced_by: usize,
+    references: usize,
+    const_type: String,
+    selected: bool,
+}
+
+impl Row {
+    fn cmp_by(&self, other: &Row, column: TableColumn) -> Ordering {
+        match column {
+            TableColumn::Name => self.name.cmp(&other.name),
+            TableColumn::Category => self.category.cmp(&other.category),
+            TableColumn::ReferencedBy => self.referenced_by.cmp(&other.referenced_by),
+            TableColumn::References => self.references.cmp(&other.references),
+            TableColumn::Type => self.const_type.cmp(&other.const_type),
+        }
+    }
+}
+
+/// Sortable list of the nodes of the filtered graph shown below the graph
+pub struct NodeTable {
+    pub open: bool,
+    sort_column: TableColumn,
+    descending: bool,
+}
+
+impl Default for NodeTable {
+    fn default() -> Self {
+        Self {
+            open: false,
+            sort_column: TableColumn::ReferencedBy,
+            descending: true,
+        }
+    }
+}
+
+impl NodeTable {
+    /// Shows the table, returns the node clicked or moved to with the arrow keys
+    pub fn show(&mut self, ctx: &egui::Context, g: &G, keyboard: bool) -> Option<NodeIndex<u32>> {
+        if !self.open {
+            return None;
+        }
+
+        let mut rows = g
+            .g
+            .node_indices()
+            .map(|ni| {
+                let payload = g.g[ni].payload();
+                Row {
+                    ni,
+                    name: payload.name.clone(),
+                    category: format!("{:?}", payload.const_category),
+                    referenced_by: g.g.neighbors_directed(ni, Direction::Outgoing).count(),
+                    references: g.g.neighbors_directed(ni, Direction::Incoming).count(),
+                    const_type: payload.const_type.chars().take(MAX_TYPE_LEN).collect(),
+                    selected: g.g[ni].selected(),
+                }
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| {
+            let ord = a.cmp_by(b, self.sort_column);
+            if self.descending {
+                ord.reverse()
+            } else {
+                ord
+            }
+        });
+
+        let mut chosen = None;
+        let mut scroll_to = None;
+        if keyboard && !ctx.wants_keyboard_input() {
+            let (up, down) = ctx.input_mut(|i| {
+                (
+                    i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                    i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                )
+            });
+            let current = rows.iter().position(|row| row.selected);
+            let next = match (current, up, down) {
+                (Some(i), true, _) => Some(i.saturating_sub(1)),
+                (Some(i), _, true) => Some((i + 1).min(rows.len().saturating_sub(1))),
+                (None, true, _) | (None, _, true) if !rows.is_empty() => Some(0),
+                _ => None,
+            };
+            if let Some(i) = next {
+                chosen = Some(rows[i].ni);
+                scroll_to = Some(i);
+            }
+        }
+
+        egui::TopBottomPanel::bottom("node_table").resizable(true).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (column, width) in TableColumn::ALL.into_iter().zip(COLUMN_WIDTHS) {
+                    let arrow = match (column == self.sort_column, self.descending) {
+                        (true, true) => " ⏷",
+                        (true, false) => " ⏶",
+                        _ => "",
+                    };
+                    let header = egui::SelectableLabel::new(column == self.sort_column, format!("{}{arrow}", column.label()));
+                    if ui.add_sized([width, ROW_HEIGHT], header).clicked() {
+                        if column == self.sort_column {
+                            self.descending = !self.descending;
+                        } else {
+                            self.sort_column = column;
+                            self.descending = false;
+                        }
+                    }
+                }
+            });
+            ui.separator();
+
+            let mut scroll_area = egui::ScrollArea::both().auto_shrink([false, false]);
+            if let Some(i) = scroll_to {
+                let row_height = ROW_HEIGHT + ui.spacing().item_spacing.y;
+                scroll_area = scroll_area.vertical_scroll_offset((i as f32 * row_height - ui.available_height() / 2.).max(0.));
+            }
+            scroll_area.show_rows(ui, ROW_HEIGHT, rows.len(), |ui, range| {
+                for row in &rows[range] {
+                    ui.horizontal(|ui| {
+                        let cells = [
+                            row.name.clone(),
+                            row.category.clone(),
+                            row.referenced_by.to_string(),
+                            row.references.to_string(),
+                            row.const_type.clone(),
+                        ];
+                        for (cell, width) in cells.into_iter().zip(COLUMN_WIDTHS) {
+                            let label = egui::SelectableLabel::new(row.selected, cell);
+                            if ui.add_sized([width, ROW_HEIGHT], label).clicked() {
+                                chosen = Some(row.ni);
+                            }
+                        }
+                    });
+                }
+            });
+        });
+
+        chosen
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use egui::Pos2;
+use petgraph::graph::NodeIndex;
+
+use crate::{ColoringSettings, FilterSettings, NodePayload};
+
+const MAX_UNDO_ENTRIES: usize = 100;
+
+/// Part of a node that can be changed by the user and undone
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeState {
+    color: [f32; 3],
+    color_locked: bool,
+    pinned: bool,
+    fixed_location: Option<Pos2>,
+}
+
+impl NodeState {
+    pub fn of(payload: &NodePayload) -> Self {
+        Self {
+            color: payload.color,
+            color_locked: payload.color_locked,
+            pinned: payload.pinned,
+            fixed_location: payload.fixed_location,
+        }
+    }
+    pub fn apply(&self, payload: &mut NodePayload) {
+        payload.color = self.color;
+        payload.color_locked = self.color_locked;
+        payload.pinned = self.pinned;
+        payload.fixed_location = self.fixed_location;
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct UndoSettings {
+    pub filter_settings: FilterSettings,
+    pub coloring_settings: ColoringSettings,
+}
+
+/// State before an action, only nodes changed by the action are stored
+struct UndoEntry {
+    settings: UndoSettings,
+    nodes: Vec<(NodeIndex<u32>, NodeState)>,
+}
+
+#[derive(Default)]
+pub struct UndoStack {
+    /// Last committed state, the entries are differences to it
+    current: Option<(UndoSettings, HashMap<NodeIndex<u32>, NodeState>)>,
+    undo: Vec<UndoEntry>,
+    redo: Vec<UndoEntry>,
+}
+
+impl UndoStack {
+    pub fn reset(&mut self) {
+        *self = Default::default();
+    }
+
+    /// Records the difference to the last committed state as a single undo entry
+    pub fn commit(&mut self, settings: UndoSettings, nodes: HashMap<NodeIndex<u32>, NodeState>) {
+        let Some((prev_settings, prev_nodes)) = self.current.take() else {
+            self.current = Some((settings, nodes));
+            return;
+        };
+
+        let changed = nodes
+            .iter()
+            .filter_map(|(ni, state)| {
+                let prev_state = prev_nodes.get(ni)?;
+                (prev_state != state).then(|| (*ni, prev_state.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        if prev_settings != settings || !changed.is_empty() {
+            self.undo.push(UndoEntry {
+                settings: prev_settings,
+                nodes: changed,
+            });
+            if self.undo.len() > MAX_UNDO_ENTRIES {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+        self.current = Some((settings, nodes));
+    }
+
+    /// Returns the state to restore
+    pub fn undo(&mut self) -> Option<(UndoSettings, Vec<(NodeIndex<u32>, NodeState)>)> {
+        Self::step(&mut self.current, &mut self.undo, &mut self.redo)
+    }
+
+    /// Returns the state to restore
+    pub fn redo(&mut self) -> Option<(UndoSettings, Vec<(NodeIndex<u32>, NodeState)>)> {
+        Self::step(&mut self.current, &mut self.redo, &mut self.undo)
+    }
+
+    fn step(
+        current: &mut Option<(UndoSettings, HashMap<NodeIndex<u32>, NodeState>)>,
+        from: &mut Vec<UndoEntry>,
+        to: &mut Vec<UndoEntry>,
+    ) -> Option<(UndoSettings, Vec<(NodeIndex<u32>, NodeState)>)> {
+        let (settings, nodes) = current.as_mut()?;
+        let entry = from.pop()?;
+
+        // remember the current state of the same nodes to be able to step back
+        to.push(UndoEntry {
+            settings: settings.clone(),
+            nodes: entry
+                .nodes
+                .iter()
+                .filter_map(|(ni, _)| Some((*ni, nodes.get(ni)?.clone())))
+                .collect(),
+        });
+
+        *settings = entry.settings.clone();
+        for (ni, state) in &entry.nodes {
+            nodes.insert(*ni, state.clone());
+        }
+
+        Some((entry.settings, entry.nodes))
+    }
+}
@@ -106,18 +106,31 @@ def nameToString (n : Name) : String :=
   toString n
 
 
+-- Get the source file and line where the constant is declared, if known
+def getSourceLocation (n : Name) : TermElabM (List (String × Json)) := do
+  let some modName ← findModuleOf? n | return []
+  let file := (modName.toString.replace "." "/").append ".lean"
+  let line := match ← findDeclarationRanges? n with
+    | some ranges => [("sourceLine", toJson ranges.range.pos.line)]
+    | none => []
+  return ("sourceFile", Json.str file) :: line
+
 -- Convert a Name and List Name pair to JSON
 def pairToJson (pair : Name × List Name) : TermElabM (Option Json) := do
   let nameStr := nameToString pair.fst
   let constCategoryStr ← try (getConstType pair.fst) catch | _ => return none
   let nameListStr := pair.snd.map nameToString
   let constTypeStr ← getTypeStr pair.fst
-  return Json.mkObj [("name", Json.str nameStr),("constCategory", Json.str constCategoryStr), ("constType", constTypeStr), ("references", Json.arr (nameListStr.map Json.str).toArray)]
+  let location ← try getSourceLocation pair.fst catch | _ => pure []
+  return Json.mkObj ([("name", Json.str nameStr),("constCategory", Json.str constCategoryStr), ("constType", constTypeStr), ("references", Json.arr (nameListStr.map Json.str).toArray)] ++ location)
+
+-- Version of the emitted format, increase when fields are changed
+def schemaVersion : Nat := 1
 
 -- Serialize a List (Name, List Name) to JSON
 def serializeList (l : List (Name × List Name)) : TermElabM Json := do
   let res ← (l.filterMapM pairToJson)
-  return Json.arr res.toArray
+  return Json.mkObj [("version", toJson schemaVersion), ("nodes", Json.arr res.toArray)]
 
 inductive Source
 | Namespace (n : String)